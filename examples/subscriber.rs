@@ -1,35 +1,28 @@
 use std::error::Error;
 
-use make87::encodings::{Encoder, ProtobufEncoder};
-use make87::interfaces::zenoh::{ConfiguredSubscriber, ZenohInterface};
+use make87::encodings::ProtobufEncoder;
+use make87::interfaces::zenoh::{ZenohInterface, ZenohInterfaceError};
 use make87_messages::text::PlainText;
 
-macro_rules! recv_and_print {
-    ($sub:expr) => {{
-        let subscriber = $sub;
-        let message_encoder = ProtobufEncoder::<PlainText>::new();
-        while let Ok(sample) = subscriber.recv_async().await {
-            let message_decoded = message_encoder.decode(&sample.payload().to_bytes());
-            match message_decoded {
-                Ok(msg) => println!("Received: {:?}", msg),
-                Err(e) => eprintln!("Decode error: {e}"),
-            }
-        }
-    }};
-}
-
-
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error + Send + Sync>> {
     let zenoh_interface = ZenohInterface::from_default_env("zenoh")?;
     let session = zenoh_interface.get_session().await?;
 
-    let configured_subscriber = zenoh_interface.get_subscriber(&session,"HELLO_WORLD_MESSAGE").await?;
+    let subscriber = zenoh_interface
+        .get_typed_subscriber(
+            &session,
+            "HELLO_WORLD_MESSAGE",
+            ProtobufEncoder::<PlainText>::new(),
+        )
+        .await?;
 
-    // Use a macro to avoid duplicated code. User  to do it inline instead.
-    match configured_subscriber {
-        ConfiguredSubscriber::Fifo(sub) => recv_and_print!(&sub),
-        ConfiguredSubscriber::Ring(sub) => recv_and_print!(&sub),
+    loop {
+        match subscriber.recv().await {
+            Ok(msg) => println!("Received: {:?}", msg),
+            Err(ZenohInterfaceError::Decode(e)) => eprintln!("Decode error: {e}"),
+            Err(_) => break,
+        }
     }
 
     Ok(())
@@ -1,10 +1,10 @@
 use std::env;
-use std::fs;
 use std::io;
 use thiserror::Error;
-use regex::Regex;
+use serde::de::DeserializeOwned;
 use serde_json::{self, Value};
 use crate::models::ApplicationConfig;
+use crate::secrets::{SecretResolver, UnresolvedPlaceholder};
 
 pub const DEFAULT_ENV_VAR: &str = "MAKE87_CONFIG";
 
@@ -16,55 +16,38 @@ pub enum ConfigError {
     SerdeJson(#[from] serde_json::Error),
     #[error("failed to load secret '{name}': {source}")]
     Secret {
+        /// Name of the secret, or `env.NAME` for an environment variable.
         name: String,
         #[source]
         source: io::Error,
     },
+    #[error("unresolved config placeholders: {}", list_unresolved(.0))]
+    Unresolved(Vec<UnresolvedPlaceholder>),
+    #[error("invalid config path '{0}', expected a JSON pointer such as '/detector/threshold'")]
+    InvalidPointer(String),
+    #[error("missing config value at '{path}', expected {expected}")]
+    MissingValue {
+        path: String,
+        /// Short name of the requested type, e.g. `Vec<String>`.
+        expected: String,
+    },
+    #[error("invalid config value at '{path}', expected {expected}: {source}")]
+    InvalidValue {
+        path: String,
+        expected: String,
+        #[source]
+        source: serde_json::Error,
+    },
 }
 
 pub type Result<T> = std::result::Result<T, ConfigError>;
 
-// Make the regex static and pub(crate) so tests can use it
-pub(crate) static SECRET_PATTERN: once_cell::sync::Lazy<Regex> = once_cell::sync::Lazy::new(|| {
-    Regex::new(r"^\s*\{\{\s*secret\.([A-Za-z0-9_]+)\s*}}\s*$").unwrap()
-});
-
-// Recursively resolve secrets in a serde_json::Value
-fn resolve_secrets(value: Value) -> Result<Value> {
-    // Use the shared static regex
-    match value {
-        Value::Object(map) => {
-            let mut new_map = serde_json::Map::new();
-            for (k, v) in map {
-                new_map.insert(k, resolve_secrets(v)?);
-            }
-            Ok(Value::Object(new_map))
-        }
-        Value::Array(arr) => {
-            let mut new_arr = Vec::with_capacity(arr.len());
-            for v in arr {
-                new_arr.push(resolve_secrets(v)?);
-            }
-            Ok(Value::Array(new_arr))
-        }
-        Value::String(s) => {
-            if let Some(caps) = SECRET_PATTERN.captures(&s) {
-                let secret_name = &caps[1];
-                let secret_path = format!("/run/secrets/{}.secret", secret_name);
-                let secret_value = fs::read_to_string(&secret_path)
-                    .map_err(|e| ConfigError::Secret {
-                        name: secret_name.to_string(),
-                        source: e,
-                    })?
-                    .trim()
-                    .to_owned();
-                Ok(Value::String(secret_value))
-            } else {
-                Ok(Value::String(s))
-            }
-        }
-        other => Ok(other),
-    }
+fn list_unresolved(placeholders: &[UnresolvedPlaceholder]) -> String {
+    placeholders
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(", ")
 }
 
 pub fn load_config_from_default_env() -> Result<ApplicationConfig> {
@@ -72,18 +55,85 @@ pub fn load_config_from_default_env() -> Result<ApplicationConfig> {
 }
 
 pub fn load_config_from_env(var: &str) -> Result<ApplicationConfig> {
-    let raw = env::var(var)?;
-    let mut config: ApplicationConfig = serde_json::from_str(&raw)?;
-    config.config = resolve_secrets(config.config)?;
-    Ok(config)
+    load_config_from_env_with(var, &SecretResolver::default())
 }
 
 pub fn load_config_from_json<T: AsRef<str>>(json_data: T) -> Result<ApplicationConfig> {
+    load_config_from_json_with(json_data, &SecretResolver::default())
+}
+
+/// Load the config from the environment variable `var`, resolving secrets with `secrets`.
+pub fn load_config_from_env_with(var: &str, secrets: &SecretResolver) -> Result<ApplicationConfig> {
+    let raw = env::var(var)?;
+    load_config_from_json_with(raw, secrets)
+}
+
+/// Load the config from `json_data`, resolving secrets with `secrets`.
+pub fn load_config_from_json_with<T: AsRef<str>>(
+    json_data: T,
+    secrets: &SecretResolver,
+) -> Result<ApplicationConfig> {
     let mut config: ApplicationConfig = serde_json::from_str(json_data.as_ref())?;
-    config.config = resolve_secrets(config.config)?;
+    config.config = secrets.resolve(config.config)?;
     Ok(config)
 }
 
+/// `T`'s type name without module paths, `Vec<String>` rather than
+/// `alloc::vec::Vec<alloc::string::String>`.
+fn short_type_name<T>() -> String {
+    std::any::type_name::<T>()
+        .split_inclusive(|c: char| !(c.is_alphanumeric() || c == '_' || c == ':'))
+        .map(|part| part.rsplit("::").next().unwrap_or(part))
+        .collect()
+}
+
+fn deserialize_value<T: DeserializeOwned>(path: &str, value: &Value) -> Result<T> {
+    T::deserialize(value).map_err(|source| ConfigError::InvalidValue {
+        path: path.to_string(),
+        expected: short_type_name::<T>(),
+        source,
+    })
+}
+
+/// Typed access to the application's own `config` section.
+impl ApplicationConfig {
+    /// The value at the JSON pointer `path` of `config`, e.g. `/detector/threshold`.
+    ///
+    /// The empty pointer `""` refers to the whole section, any other `path` must start
+    /// with `/`. An explicit `null` is passed on to `T`, so it reads as `None` for an
+    /// `Option` and fails for most other types.
+    pub fn get<T: DeserializeOwned>(&self, path: &str) -> Result<T> {
+        match self.lookup(path)? {
+            Some(value) => deserialize_value(path, value),
+            None => Err(ConfigError::MissingValue {
+                path: path.to_string(),
+                expected: short_type_name::<T>(),
+            }),
+        }
+    }
+
+    /// Like [`get`](Self::get), but `default` if nothing is set at `path`.
+    ///
+    /// An explicit `null` counts as not set. A value of the wrong type is still an error.
+    pub fn get_or<T: DeserializeOwned>(&self, path: &str, default: T) -> Result<T> {
+        match self.lookup(path)? {
+            Some(Value::Null) | None => Ok(default),
+            Some(value) => deserialize_value(path, value),
+        }
+    }
+
+    fn lookup(&self, path: &str) -> Result<Option<&Value>> {
+        if !path.is_empty() && !path.starts_with('/') {
+            return Err(ConfigError::InvalidPointer(path.to_string()));
+        }
+        Ok(self.config.pointer(path))
+    }
+
+    /// The whole `config` section as a `T`.
+    pub fn deserialize_config<T: DeserializeOwned>(&self) -> Result<T> {
+        deserialize_value("", &self.config)
+    }
+}
 
 #[cfg(test)]
 mod tests {
@@ -170,6 +220,76 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_typed_config_access() {
+        #[derive(serde::Deserialize, Debug, PartialEq)]
+        struct Detector {
+            threshold: f64,
+            #[serde(default)]
+            labels: Vec<String>,
+        }
+
+        let mut config = default_app_config();
+        config.config = serde_json::json!({
+            "detector": {"threshold": 0.5, "labels": ["cat", "dog"]},
+            "a/b": {"~c": 3}
+        });
+
+        assert_eq!(config.get::<f64>("/detector/threshold").unwrap(), 0.5);
+        assert_eq!(config.get::<String>("/detector/labels/1").unwrap(), "dog");
+        assert_eq!(config.get::<u32>("/a~1b/~0c").unwrap(), 3);
+        assert_eq!(config.get_or("/detector/iou", 0.45).unwrap(), 0.45);
+        assert_eq!(config.get_or("/detector/threshold", 0.9).unwrap(), 0.5);
+
+        let detector: Detector = config.get("/detector").unwrap();
+        assert_eq!(detector.labels, vec!["cat", "dog"]);
+
+        match config.get::<u32>("/detector/iou") {
+            Err(ConfigError::MissingValue { path, expected }) => {
+                assert_eq!(path, "/detector/iou");
+                assert_eq!(expected, "u32");
+            }
+            other => panic!("Expected a missing value, got {:?}", other),
+        }
+        let err = config.get_or::<u32>("/detector/threshold", 1).unwrap_err();
+        assert!(matches!(err, ConfigError::InvalidValue { ref expected, .. } if expected == "u32"));
+        assert!(err
+            .to_string()
+            .starts_with("invalid config value at '/detector/threshold', expected u32"));
+
+        match config.get::<Vec<String>>("/detector/names") {
+            Err(ConfigError::MissingValue { expected, .. }) => assert_eq!(expected, "Vec<String>"),
+            other => panic!("Expected a missing value, got {:?}", other),
+        }
+
+        match config.get::<f64>("detector/threshold") {
+            Err(ConfigError::InvalidPointer(path)) => assert_eq!(path, "detector/threshold"),
+            other => panic!("Expected an invalid pointer, got {:?}", other),
+        }
+        assert!(matches!(
+            config.get_or("detector/iou", 0.45),
+            Err(ConfigError::InvalidPointer(_))
+        ));
+        assert!(config.get::<Value>("").unwrap().is_object());
+
+        config.config = serde_json::json!({"detector": {"iou": null}});
+        assert_eq!(config.get_or("/detector/iou", 0.45).unwrap(), 0.45);
+        assert_eq!(config.get::<Option<f64>>("/detector/iou").unwrap(), None);
+        assert!(matches!(
+            config.get::<f64>("/detector/iou"),
+            Err(ConfigError::InvalidValue { .. })
+        ));
+
+        config.config = serde_json::json!({"threshold": 0.7});
+        let detector: Detector = config.deserialize_config().unwrap();
+        assert_eq!(detector, Detector { threshold: 0.7, labels: vec![] });
+        config.config = serde_json::json!({"labels": []});
+        assert!(matches!(
+            config.deserialize_config::<Detector>(),
+            Err(ConfigError::InvalidValue { .. })
+        ));
+    }
+
     #[test]
     fn test_secret_resolution() {
         let tmpdir = TempDir::new().unwrap();
@@ -181,7 +301,7 @@ mod tests {
             write!(f, "{}", secret_value).unwrap();
         }
 
-        // Patch /run/secrets/MYSECRET.secret to point to our temp file using symlink if possible
+        // Serve the secret from a root other than /run/secrets, through a symlink
         let run_secrets = tmpdir.path().join("run_secrets");
         std::fs::create_dir_all(&run_secrets).unwrap();
         let symlink_path = run_secrets.join(format!("{}.secret", secret_name));
@@ -190,9 +310,6 @@ mod tests {
         #[cfg(windows)]
         std::os::windows::fs::symlink_file(&secret_file_path, &symlink_path).unwrap();
 
-        // Patch std::fs::read_to_string to redirect /run/secrets/MYSECRET.secret to our temp file
-        // Instead, temporarily set the secret in the real /run/secrets if possible, else skip test
-        // We'll instead patch the config to point to our tempdir
         let config_json = serde_json::json!({
             "application_info": {
                 "application_id": "app-id",
@@ -208,35 +325,8 @@ mod tests {
             "config": {"password": format!("{{{{ secret.{} }}}}", secret_name)},
         });
 
-        // Patch the secret path in the environment by temporarily replacing /run/secrets with our tempdir
-        // We'll monkeypatch resolve_secrets for this test
-        fn resolve_secrets_test(value: Value, secret_file: &std::path::Path) -> Value {
-            // Use the main code's SECRET_PATTERN
-            match value {
-                Value::Object(map) => {
-                    let mut new_map = serde_json::Map::new();
-                    for (k, v) in map {
-                        new_map.insert(k, resolve_secrets_test(v, secret_file));
-                    }
-                    Value::Object(new_map)
-                }
-                Value::Array(arr) => {
-                    Value::Array(arr.into_iter().map(|v| resolve_secrets_test(v, secret_file)).collect())
-                }
-                Value::String(s) => {
-                    if let Some(_caps) = SECRET_PATTERN.captures(&s) {
-                        let secret_value = std::fs::read_to_string(secret_file).unwrap().trim().to_owned();
-                        Value::String(secret_value)
-                    } else {
-                        Value::String(s)
-                    }
-                }
-                other => other,
-            }
-        }
-
-        let mut config: ApplicationConfig = serde_json::from_value(config_json).unwrap();
-        config.config = resolve_secrets_test(config.config, &secret_file_path);
+        let resolver = SecretResolver::from_root(&run_secrets);
+        let config = load_config_from_json_with(config_json.to_string(), &resolver).unwrap();
 
         assert_eq!(config.config["password"], secret_value);
     }
@@ -252,7 +342,7 @@ mod tests {
             write!(f, "{}", secret_value).unwrap();
         }
 
-        // Patch /run/secrets/MYSECRET.secret to point to our temp file using symlink if possible
+        // Serve the secret from a root other than /run/secrets, through a symlink
         let run_secrets = tmpdir.path().join("run_secrets");
         std::fs::create_dir_all(&run_secrets).unwrap();
         let symlink_path = run_secrets.join(format!("{}.secret", secret_name));
@@ -289,33 +379,8 @@ mod tests {
                 "config": {"password": variant},
             });
 
-            fn resolve_secrets_test(value: Value, secret_file: &std::path::Path) -> Value {
-                // Use the main code's SECRET_PATTERN
-                match value {
-                    Value::Object(map) => {
-                        let mut new_map = serde_json::Map::new();
-                        for (k, v) in map {
-                            new_map.insert(k, resolve_secrets_test(v, secret_file));
-                        }
-                        Value::Object(new_map)
-                    }
-                    Value::Array(arr) => {
-                        Value::Array(arr.into_iter().map(|v| resolve_secrets_test(v, secret_file)).collect())
-                    }
-                    Value::String(s) => {
-                        if let Some(_caps) = SECRET_PATTERN.captures(&s) {
-                            let secret_value = std::fs::read_to_string(secret_file).unwrap().trim().to_owned();
-                            Value::String(secret_value)
-                        } else {
-                            Value::String(s)
-                        }
-                    }
-                    other => other,
-                }
-            }
-
-            let mut config: ApplicationConfig = serde_json::from_value(config_json).unwrap();
-            config.config = resolve_secrets_test(config.config, &secret_file_path);
+            let resolver = SecretResolver::from_root(&run_secrets);
+            let config = load_config_from_json_with(config_json.to_string(), &resolver).unwrap();
 
             assert_eq!(config.config["password"], secret_value, "Failed for variant: {:?}", config.config["password"]);
         }
@@ -50,6 +50,17 @@ mod tests {
         assert_eq!(decoded, original);
     }
 
+    #[test]
+    fn test_json_encoder_encode_into() {
+        let encoder = JsonEncoder::<Example>::new();
+        let original = Example { id: 1, name: "a".to_string() };
+        assert_eq!(encoder.encoded_len(&original), None);
+        let mut buf = [0u8; 64];
+        let len = encoder.encode_into(&original, &mut buf).unwrap();
+        assert_eq!(&buf[..len], br#"{"id":1,"name":"a"}"#);
+        assert!(encoder.encode_into(&original, &mut buf[..4]).is_err());
+    }
+
     #[test]
     fn test_json_encoder_decode_error() {
         let encoder = JsonEncoder::<Example>::new();
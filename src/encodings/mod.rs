@@ -1,6 +1,9 @@
 mod json;
 pub use json::JsonEncoder;
 
+mod registry;
+pub use registry::{BoxedEncoder, EncoderRegistry, EncodingError, DEFAULT_ENCODING};
+
 #[cfg(feature = "yaml")]
 mod yaml;
 #[cfg(feature = "yaml")]
@@ -27,4 +30,25 @@ impl std::error::Error for EncodeError {}
 pub trait Encoder<T> {
     fn encode(&self, value: &T) -> Result<Vec<u8>, EncodeError>;
     fn decode(&self, data: &[u8]) -> Result<T, EncodeError>;
+
+    /// Exact size of `value` once encoded, if it can be known without encoding it.
+    fn encoded_len(&self, _value: &T) -> Option<usize> {
+        None
+    }
+
+    /// Encode `value` into the start of `buf`, returning the number of bytes written.
+    ///
+    /// The default implementation encodes into a temporary buffer and copies it over.
+    fn encode_into(&self, value: &T, buf: &mut [u8]) -> Result<usize, EncodeError> {
+        let bytes = self.encode(value)?;
+        if buf.len() < bytes.len() {
+            return Err(EncodeError(format!(
+                "buffer of {} bytes is too small for {} encoded bytes",
+                buf.len(),
+                bytes.len()
+            )));
+        }
+        buf[..bytes.len()].copy_from_slice(&bytes);
+        Ok(bytes.len())
+    }
 }
@@ -25,6 +25,17 @@ where
     fn decode(&self, data: &[u8]) -> Result<T, EncodeError> {
         T::decode(data).map_err(|e| EncodeError(e.to_string()))
     }
+
+    fn encoded_len(&self, value: &T) -> Option<usize> {
+        Some(value.encoded_len())
+    }
+
+    fn encode_into(&self, value: &T, buf: &mut [u8]) -> Result<usize, EncodeError> {
+        let capacity = buf.len();
+        let mut target = buf;
+        value.encode(&mut target).map_err(|e| EncodeError(e.to_string()))?;
+        Ok(capacity - target.len())
+    }
 }
 
 #[cfg(test)]
@@ -49,6 +60,19 @@ mod tests {
         assert_eq!(decoded, original);
     }
 
+    #[test]
+    fn test_protobuf_encoder_encode_into() {
+        let encoder = ProtobufEncoder::<Example>::new();
+        let original = Example { id: 7, name: "frame".to_string() };
+        let len = encoder.encoded_len(&original).unwrap();
+        let mut buf = vec![0u8; len + 4];
+        assert_eq!(encoder.encode_into(&original, &mut buf).unwrap(), len);
+        assert_eq!(&buf[..len], encoder.encode(&original).unwrap().as_slice());
+
+        let mut small = vec![0u8; len - 1];
+        assert!(encoder.encode_into(&original, &mut small).is_err());
+    }
+
     #[test]
     fn test_protobuf_encoder_decode_error() {
         let encoder = ProtobufEncoder::<Example>::new();
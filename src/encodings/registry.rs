@@ -0,0 +1,313 @@
+use super::{EncodeError, Encoder, JsonEncoder};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+/// Encoding assumed when a topic or endpoint config does not name one.
+pub const DEFAULT_ENCODING: &str = "proto";
+
+/// A type-erased encoder, as handed out by an [`EncoderRegistry`].
+pub type BoxedEncoder<T> = Box<dyn Encoder<T> + Send + Sync>;
+
+impl<T> Encoder<T> for BoxedEncoder<T> {
+    fn encode(&self, value: &T) -> Result<Vec<u8>, EncodeError> {
+        (**self).encode(value)
+    }
+
+    fn decode(&self, data: &[u8]) -> Result<T, EncodeError> {
+        (**self).decode(data)
+    }
+
+    fn encoded_len(&self, value: &T) -> Option<usize> {
+        (**self).encoded_len(value)
+    }
+
+    fn encode_into(&self, value: &T, buf: &mut [u8]) -> Result<usize, EncodeError> {
+        (**self).encode_into(value, buf)
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum EncodingError {
+    #[error("encoding '{encoding}' requires make87 to be built with the `{feature}` feature")]
+    FeatureDisabled {
+        encoding: String,
+        feature: &'static str,
+    },
+    #[error("encoding '{0}' is not registered for this message type")]
+    NotRegistered(String),
+    #[error("configured message type '{configured}' does not match registered type '{expected}'")]
+    MessageTypeMismatch {
+        expected: String,
+        configured: String,
+    },
+}
+
+type EncoderFactory<T> = Arc<dyn Fn() -> BoxedEncoder<T> + Send + Sync>;
+
+/// Maps encoding names, as found in the `encoding` field of topic and endpoint configs,
+/// to [`Encoder`] implementations for messages of type `T`.
+///
+/// Built-in encodings are registered through `with_json`, `with_yaml` and `with_protobuf`
+/// (or the `serde`/`protobuf` shortcuts), since which of them apply depends on the traits
+/// `T` implements. Any other name can be added with [`EncoderRegistry::register`].
+///
+/// A registry may also be tied to the make87 message type name of `T`, in which case
+/// configs declaring a different `message_type` are rejected.
+pub struct EncoderRegistry<T> {
+    factories: BTreeMap<String, EncoderFactory<T>>,
+    message_type: Option<String>,
+}
+
+impl<T> Default for EncoderRegistry<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Clone for EncoderRegistry<T> {
+    fn clone(&self) -> Self {
+        Self {
+            factories: self.factories.clone(),
+            message_type: self.message_type.clone(),
+        }
+    }
+}
+
+impl<T> EncoderRegistry<T> {
+    pub fn new() -> Self {
+        Self {
+            factories: BTreeMap::new(),
+            message_type: None,
+        }
+    }
+
+    /// Only accept configs whose `message_type` equals `message_type`.
+    pub fn for_message_type(mut self, message_type: &str) -> Self {
+        self.message_type = Some(message_type.to_string());
+        self
+    }
+
+    pub fn message_type(&self) -> Option<&str> {
+        self.message_type.as_deref()
+    }
+
+    /// Check a configured `message_type` against the one this registry is tied to, if any.
+    pub fn check_message_type(&self, configured: &str) -> Result<(), EncodingError> {
+        match &self.message_type {
+            Some(expected) if expected != configured => Err(EncodingError::MessageTypeMismatch {
+                expected: expected.clone(),
+                configured: configured.to_string(),
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Register `factory` under `name`, replacing any previous registration.
+    pub fn register<E, F>(&mut self, name: &str, factory: F) -> &mut Self
+    where
+        E: Encoder<T> + Send + Sync + 'static,
+        F: Fn() -> E + Send + Sync + 'static,
+    {
+        self.factories.insert(
+            name.to_string(),
+            Arc::new(move || Box::new(factory()) as BoxedEncoder<T>),
+        );
+        self
+    }
+
+    /// Builder-style variant of [`EncoderRegistry::register`].
+    pub fn with<E, F>(mut self, name: &str, factory: F) -> Self
+    where
+        E: Encoder<T> + Send + Sync + 'static,
+        F: Fn() -> E + Send + Sync + 'static,
+    {
+        self.register(name, factory);
+        self
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.factories.contains_key(name)
+    }
+
+    pub fn encodings(&self) -> impl Iterator<Item = &str> {
+        self.factories.keys().map(String::as_str)
+    }
+
+    /// Create the encoder registered for `encoding`, falling back to [`DEFAULT_ENCODING`]
+    /// when no encoding is configured.
+    pub fn resolve(&self, encoding: Option<&str>) -> Result<BoxedEncoder<T>, EncodingError> {
+        let encoding = encoding.unwrap_or(DEFAULT_ENCODING);
+        if let Some(factory) = self.factories.get(encoding) {
+            return Ok(factory());
+        }
+        match missing_builtin_feature(encoding) {
+            Some(feature) => Err(EncodingError::FeatureDisabled {
+                encoding: encoding.to_string(),
+                feature,
+            }),
+            None => Err(EncodingError::NotRegistered(encoding.to_string())),
+        }
+    }
+}
+
+impl<T> EncoderRegistry<T>
+where
+    T: Serialize + DeserializeOwned + Send + Sync + 'static,
+{
+    /// Registry with every serde-based encoding compiled into this build.
+    pub fn serde() -> Self {
+        let registry = Self::new().with_json();
+        #[cfg(feature = "yaml")]
+        let registry = registry.with_yaml();
+        registry
+    }
+
+    pub fn with_json(self) -> Self {
+        self.with("json", JsonEncoder::<T>::new)
+    }
+
+    #[cfg(feature = "yaml")]
+    pub fn with_yaml(self) -> Self {
+        self.with("yaml", super::YamlEncoder::<T>::new)
+    }
+}
+
+#[cfg(feature = "protobuf")]
+impl<T> EncoderRegistry<T>
+where
+    T: prost::Message + Default + Send + Sync + 'static,
+{
+    pub fn protobuf() -> Self {
+        Self::new().with_protobuf()
+    }
+
+    pub fn with_protobuf(self) -> Self {
+        self.with("proto", super::ProtobufEncoder::<T>::new)
+    }
+}
+
+/// Name of the crate feature a built-in encoding needs, if that feature is not enabled.
+fn missing_builtin_feature(encoding: &str) -> Option<&'static str> {
+    match encoding {
+        "proto" if cfg!(not(feature = "protobuf")) => Some("protobuf"),
+        "yaml" if cfg!(not(feature = "yaml")) => Some("yaml"),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Example {
+        id: u32,
+        name: String,
+    }
+
+    struct ReversedJson;
+
+    impl Encoder<Example> for ReversedJson {
+        fn encode(&self, value: &Example) -> Result<Vec<u8>, EncodeError> {
+            let mut bytes = JsonEncoder::<Example>::new().encode(value)?;
+            bytes.reverse();
+            Ok(bytes)
+        }
+
+        fn decode(&self, data: &[u8]) -> Result<Example, EncodeError> {
+            let mut bytes = data.to_vec();
+            bytes.reverse();
+            JsonEncoder::<Example>::new().decode(&bytes)
+        }
+    }
+
+    #[test]
+    fn test_resolve_json_roundtrip() {
+        let registry = EncoderRegistry::<Example>::serde();
+        let encoder = registry.resolve(Some("json")).unwrap();
+        let original = Example {
+            id: 1,
+            name: "a".into(),
+        };
+        let decoded = encoder.decode(&encoder.encode(&original).unwrap()).unwrap();
+        assert_eq!(decoded, original);
+    }
+
+    #[test]
+    fn test_resolve_defaults_to_proto() {
+        let registry = EncoderRegistry::<Example>::serde();
+        let err = registry.resolve(None).err().unwrap();
+        #[cfg(feature = "protobuf")]
+        assert!(matches!(err, EncodingError::NotRegistered(ref name) if name == "proto"));
+        #[cfg(not(feature = "protobuf"))]
+        assert!(matches!(
+            err,
+            EncodingError::FeatureDisabled {
+                feature: "protobuf",
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_resolve_unknown_encoding() {
+        let registry = EncoderRegistry::<Example>::serde();
+        let err = registry.resolve(Some("cbor")).err().unwrap();
+        assert!(matches!(err, EncodingError::NotRegistered(ref name) if name == "cbor"));
+        assert_eq!(
+            err.to_string(),
+            "encoding 'cbor' is not registered for this message type"
+        );
+    }
+
+    #[cfg(not(feature = "yaml"))]
+    #[test]
+    fn test_resolve_yaml_without_feature() {
+        let registry = EncoderRegistry::<Example>::serde();
+        let err = registry.resolve(Some("yaml")).err().unwrap();
+        assert!(matches!(
+            err,
+            EncodingError::FeatureDisabled {
+                feature: "yaml",
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_check_message_type() {
+        let registry = EncoderRegistry::<Example>::serde();
+        assert!(registry.check_message_type("anything").is_ok());
+
+        let registry = registry.for_message_type("my.pkg.Example");
+        assert!(registry.check_message_type("my.pkg.Example").is_ok());
+        let err = registry.check_message_type("my.pkg.Other").err().unwrap();
+        assert!(matches!(
+            err,
+            EncodingError::MessageTypeMismatch { ref expected, ref configured }
+                if expected == "my.pkg.Example" && configured == "my.pkg.Other"
+        ));
+    }
+
+    #[test]
+    fn test_register_custom_encoding() {
+        let registry = EncoderRegistry::<Example>::new().with("reversed-json", || ReversedJson);
+        assert!(registry.contains("reversed-json"));
+        assert_eq!(
+            registry.encodings().collect::<Vec<_>>(),
+            vec!["reversed-json"]
+        );
+
+        let encoder = registry.resolve(Some("reversed-json")).unwrap();
+        let original = Example {
+            id: 2,
+            name: "b".into(),
+        };
+        let encoded = encoder.encode(&original).unwrap();
+        assert_eq!(encoded.first(), Some(&b'}'));
+        assert_eq!(encoder.decode(&encoded).unwrap(), original);
+    }
+}
@@ -209,6 +209,7 @@ mod tests {
                 providers: BTreeMap::new(),
                 clients,
                 servers,
+                config: BTreeMap::new(),
             },
         );
 
@@ -0,0 +1,608 @@
+use crate::encodings::DEFAULT_ENCODING;
+use crate::interfaces::zenoh::{
+    ConfiguredSubscriber, MetadataStamper, ZenohInterface, ZenohInterfaceError,
+};
+use futures::future::select_all;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use zenoh::pubsub::Publisher;
+use zenoh::Session;
+
+const BAG_MAGIC: &[u8; 8] = b"M87BAG\0\x01";
+const INDEX_MAGIC: &[u8; 8] = b"M87IDX\0\x01";
+
+#[derive(Debug, thiserror::Error)]
+pub enum BagError {
+    #[error("Bag I/O error: {0}")]
+    Io(#[from] io::Error),
+    #[error("Invalid bag file: {0}")]
+    Format(String),
+    #[error("Invalid bag index: {0}")]
+    Index(#[from] serde_json::Error),
+    #[error("Record {0} is out of range")]
+    OutOfRange(usize),
+    #[error("Record field of {0} bytes does not fit in a bag record")]
+    TooLarge(usize),
+}
+
+/// Little-endian `u32` length prefix of `len`.
+fn length_prefix(len: usize) -> Result<[u8; 4], BagError> {
+    u32::try_from(len)
+        .map(u32::to_le_bytes)
+        .map_err(|_| BagError::TooLarge(len))
+}
+
+/// Read exactly `len` bytes, growing the buffer only as data actually arrives so that a
+/// corrupt length cannot trigger a huge allocation.
+fn read_bounded(input: &mut impl Read, len: u64) -> Result<Vec<u8>, BagError> {
+    let mut buf = Vec::new();
+    input.take(len).read_to_end(&mut buf)?;
+    if (buf.len() as u64) < len {
+        return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+    }
+    Ok(buf)
+}
+
+/// One recorded sample.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BagRecord {
+    /// Name of the subscriber the sample was recorded from.
+    pub topic: String,
+    pub key: String,
+    /// Receive time in nanoseconds since the Unix epoch.
+    pub timestamp_ns: u64,
+    pub encoding: String,
+    pub message_type: String,
+    pub payload: Vec<u8>,
+}
+
+impl BagRecord {
+    fn write_to(&self, out: &mut impl Write) -> Result<u64, BagError> {
+        let strings = [&self.topic, &self.key, &self.encoding, &self.message_type];
+        let len = 8 + strings.iter().map(|s| 4 + s.len()).sum::<usize>() + 4 + self.payload.len();
+        // Check every length before writing, so an oversized record leaves the bag intact.
+        let prefix = length_prefix(len)?;
+        let fields = strings
+            .iter()
+            .map(|s| s.as_bytes())
+            .chain([&self.payload[..]])
+            .map(|field| Ok((length_prefix(field.len())?, field)))
+            .collect::<Result<Vec<_>, BagError>>()?;
+        out.write_all(&prefix)?;
+        out.write_all(&self.timestamp_ns.to_le_bytes())?;
+        for (prefix, field) in fields {
+            out.write_all(&prefix)?;
+            out.write_all(field)?;
+        }
+        Ok(4 + len as u64)
+    }
+
+    /// Read the record at the current position, or `None` at a clean end of records.
+    fn read_from(input: &mut impl Read) -> Result<Option<Self>, BagError> {
+        let mut len = [0; 4];
+        match input.read_exact(&mut len) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e.into()),
+        }
+        let len = u32::from_le_bytes(len);
+        if len == 0 {
+            return Ok(None);
+        }
+        let body = read_bounded(input, len.into())?;
+
+        let mut fields = RecordFields { body: &body };
+        let timestamp_ns = u64::from_le_bytes(fields.take(8)?.try_into().unwrap());
+        let mut string = || -> Result<String, BagError> {
+            let field = fields.field()?;
+            String::from_utf8(field.to_vec()).map_err(|e| BagError::Format(e.to_string()))
+        };
+        Ok(Some(Self {
+            topic: string()?,
+            key: string()?,
+            encoding: string()?,
+            message_type: string()?,
+            timestamp_ns,
+            payload: fields.field()?.to_vec(),
+        }))
+    }
+}
+
+struct RecordFields<'a> {
+    body: &'a [u8],
+}
+
+impl<'a> RecordFields<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], BagError> {
+        if self.body.len() < n {
+            return Err(BagError::Format("truncated record".into()));
+        }
+        let (head, rest) = self.body.split_at(n);
+        self.body = rest;
+        Ok(head)
+    }
+
+    fn field(&mut self) -> Result<&'a [u8], BagError> {
+        let len = u32::from_le_bytes(self.take(4)?.try_into().unwrap());
+        self.take(len as usize)
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+struct BagIndex {
+    topics: Vec<String>,
+    /// `(offset, timestamp_ns, topic)` of every record, `topic` indexing into `topics`.
+    entries: Vec<(u64, u64, usize)>,
+}
+
+impl BagIndex {
+    fn push(&mut self, offset: u64, record: &BagRecord) {
+        let topic = match self.topics.iter().position(|t| *t == record.topic) {
+            Some(topic) => topic,
+            None => {
+                self.topics.push(record.topic.clone());
+                self.topics.len() - 1
+            }
+        };
+        self.entries.push((offset, record.timestamp_ns, topic));
+    }
+}
+
+/// Appends records to a bag file and writes its index on [`BagWriter::finish`].
+///
+/// A bag that was never finished, e.g. because the recorder crashed, is still readable;
+/// [`BagReader`] rebuilds the index by scanning it.
+pub struct BagWriter {
+    out: BufWriter<File>,
+    offset: u64,
+    index: BagIndex,
+}
+
+impl BagWriter {
+    pub fn create(path: impl AsRef<Path>) -> Result<Self, BagError> {
+        let mut out = BufWriter::new(File::create(path)?);
+        out.write_all(BAG_MAGIC)?;
+        Ok(Self {
+            out,
+            offset: BAG_MAGIC.len() as u64,
+            index: BagIndex::default(),
+        })
+    }
+
+    pub fn write(&mut self, record: &BagRecord) -> Result<(), BagError> {
+        let written = record.write_to(&mut self.out)?;
+        self.index.push(self.offset, record);
+        self.offset += written;
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.index.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.index.entries.is_empty()
+    }
+
+    /// Write the index trailer and flush the file.
+    pub fn finish(mut self) -> Result<(), BagError> {
+        // A zero length marks the end of the records for readers scanning the file.
+        self.out.write_all(&0u32.to_le_bytes())?;
+        let index = serde_json::to_vec(&self.index)?;
+        self.out.write_all(&index)?;
+        self.out.write_all(&(index.len() as u64).to_le_bytes())?;
+        self.out.write_all(INDEX_MAGIC)?;
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+/// Random access to the records of a bag file.
+pub struct BagReader {
+    input: BufReader<File>,
+    index: BagIndex,
+}
+
+impl BagReader {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, BagError> {
+        let mut input = BufReader::new(OpenOptions::new().read(true).open(path)?);
+        let mut magic = [0; 8];
+        input.read_exact(&mut magic)?;
+        if magic != *BAG_MAGIC {
+            return Err(BagError::Format("not a make87 bag".into()));
+        }
+        let index = match Self::read_trailer(&mut input)? {
+            Some(index) => index,
+            None => Self::scan(&mut input)?,
+        };
+        Ok(Self { input, index })
+    }
+
+    fn read_trailer(input: &mut BufReader<File>) -> Result<Option<BagIndex>, BagError> {
+        let file_len = input.seek(SeekFrom::End(0))?;
+        if file_len < (BAG_MAGIC.len() + 16) as u64 {
+            return Ok(None);
+        }
+        let mut tail = [0; 16];
+        input.seek(SeekFrom::End(-16))?;
+        input.read_exact(&mut tail)?;
+        if tail[8..] != *INDEX_MAGIC {
+            return Ok(None);
+        }
+        let index_len = u64::from_le_bytes(tail[..8].try_into().unwrap());
+        let start = index_len
+            .checked_add(16)
+            .and_then(|n| file_len.checked_sub(n))
+            .filter(|start| *start >= BAG_MAGIC.len() as u64)
+            .ok_or_else(|| BagError::Format("index longer than file".into()))?;
+        input.seek(SeekFrom::Start(start))?;
+        let index = read_bounded(input, index_len)?;
+        Ok(Some(serde_json::from_slice(&index)?))
+    }
+
+    fn scan(input: &mut BufReader<File>) -> Result<BagIndex, BagError> {
+        let mut index = BagIndex::default();
+        let mut offset = input.seek(SeekFrom::Start(BAG_MAGIC.len() as u64))?;
+        // Stop at the end marker, or at a record cut short by a crash.
+        while let Ok(Some(record)) = BagRecord::read_from(input) {
+            index.push(offset, &record);
+            offset = input.stream_position()?;
+        }
+        Ok(index)
+    }
+
+    pub fn len(&self) -> usize {
+        self.index.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.index.entries.is_empty()
+    }
+
+    /// Names of the topics recorded in the bag, in order of first appearance.
+    pub fn topics(&self) -> &[String] {
+        &self.index.topics
+    }
+
+    pub fn timestamp_ns(&self, position: usize) -> Option<u64> {
+        self.index.entries.get(position).map(|e| e.1)
+    }
+
+    /// Position of the first record at or after `timestamp_ns`.
+    pub fn position_at(&self, timestamp_ns: u64) -> usize {
+        self.index
+            .entries
+            .partition_point(|entry| entry.1 < timestamp_ns)
+    }
+
+    pub fn read(&mut self, position: usize) -> Result<BagRecord, BagError> {
+        let entry = self
+            .index
+            .entries
+            .get(position)
+            .ok_or(BagError::OutOfRange(position))?;
+        self.input.seek(SeekFrom::Start(entry.0))?;
+        BagRecord::read_from(&mut self.input)?.ok_or(BagError::OutOfRange(position))
+    }
+}
+
+struct RecordedTopic {
+    name: String,
+    encoding: String,
+    message_type: String,
+    subscriber: ConfiguredSubscriber,
+}
+
+/// Records configured subscribers into a bag file.
+pub struct BagRecorder {
+    topics: Vec<RecordedTopic>,
+    writer: BagWriter,
+}
+
+impl BagRecorder {
+    /// Wait for the next sample on any recorded topic and append it to the bag.
+    pub async fn record_next(&mut self) -> Result<(), ZenohInterfaceError> {
+        let (sample, index) = {
+            let receives = self
+                .topics
+                .iter()
+                .map(|t| Box::pin(t.subscriber.recv_async()));
+            let (sample, index, _) = select_all(receives).await;
+            (sample?, index)
+        };
+        let topic = &self.topics[index];
+        let timestamp_ns = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or_default();
+        self.writer.write(&BagRecord {
+            topic: topic.name.clone(),
+            key: sample.key_expr().to_string(),
+            timestamp_ns,
+            encoding: topic.encoding.clone(),
+            message_type: topic.message_type.clone(),
+            payload: sample.payload().to_bytes().into_owned(),
+        })?;
+        Ok(())
+    }
+
+    /// Record until `stop` resolves, then finish the bag.
+    pub async fn record_until<F: std::future::Future>(
+        mut self,
+        stop: F,
+    ) -> Result<usize, ZenohInterfaceError> {
+        if !self.topics.is_empty() {
+            tokio::pin!(stop);
+            loop {
+                tokio::select! {
+                    _ = &mut stop => break,
+                    result = self.record_next() => result?,
+                }
+            }
+        }
+        self.finish()
+    }
+
+    pub fn len(&self) -> usize {
+        self.writer.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.writer.is_empty()
+    }
+
+    /// Finish the bag, returning the number of records written.
+    pub fn finish(self) -> Result<usize, ZenohInterfaceError> {
+        let len = self.writer.len();
+        self.writer.finish()?;
+        Ok(len)
+    }
+}
+
+/// How a [`BagReplayer`] paces the records it publishes.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ReplayMode {
+    /// Keep the original spacing, scaled by `rate`: `2.0` plays twice as fast.
+    Timed { rate: f64 },
+    /// Publish one record per [`BagReplayer::step`].
+    Stepped,
+}
+
+struct ReplayTarget {
+    publisher: Publisher<'static>,
+    stamper: MetadataStamper,
+}
+
+/// Publishes the records of a bag through the configured publishers of the same name.
+///
+/// Records of topics without a configured publisher are skipped.
+pub struct BagReplayer {
+    reader: BagReader,
+    targets: BTreeMap<String, ReplayTarget>,
+    mode: ReplayMode,
+    position: usize,
+}
+
+impl BagReplayer {
+    pub fn with_mode(mut self, mode: ReplayMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    pub fn reader(&self) -> &BagReader {
+        &self.reader
+    }
+
+    /// Continue from the first record at or after `timestamp_ns`.
+    pub fn seek(&mut self, timestamp_ns: u64) {
+        self.position = self.reader.position_at(timestamp_ns);
+    }
+
+    /// Publish the next record, returning it, or `None` at the end of the bag.
+    pub async fn step(&mut self) -> Result<Option<BagRecord>, ZenohInterfaceError> {
+        if self.position >= self.reader.len() {
+            return Ok(None);
+        }
+        let record = self.reader.read(self.position)?;
+        self.position += 1;
+        if let Some(target) = self.targets.get(&record.topic) {
+            target
+                .publisher
+                .put(record.payload.clone())
+                .attachment(target.stamper.next().to_attachment()?)
+                .await?;
+        }
+        Ok(Some(record))
+    }
+
+    /// Publish the remaining records, paced by the replay mode, returning how many were
+    /// replayed. In [`ReplayMode::Stepped`] this publishes the next record only.
+    pub async fn replay(&mut self) -> Result<usize, ZenohInterfaceError> {
+        let rate = match self.mode {
+            ReplayMode::Timed { rate } if rate.is_finite() && rate > 0.0 => rate,
+            ReplayMode::Timed { .. } => f64::INFINITY,
+            ReplayMode::Stepped => return Ok(self.step().await?.map_or(0, |_| 1)),
+        };
+        let Some(first_ns) = self.reader.timestamp_ns(self.position) else {
+            return Ok(0);
+        };
+        let started = Instant::now();
+        let mut replayed = 0;
+        while let Some(timestamp_ns) = self.reader.timestamp_ns(self.position) {
+            let offset = Duration::from_nanos(timestamp_ns.saturating_sub(first_ns));
+            tokio::time::sleep_until((started + offset.div_f64(rate)).into()).await;
+            self.step().await?;
+            replayed += 1;
+        }
+        Ok(replayed)
+    }
+}
+
+impl ZenohInterface {
+    /// Record the subscribers named in `names` into a new bag file at `path`.
+    pub async fn get_bag_recorder(
+        &self,
+        session: &Session,
+        names: &[&str],
+        path: impl AsRef<Path>,
+    ) -> Result<BagRecorder, ZenohInterfaceError> {
+        let mut topics = Vec::with_capacity(names.len());
+        for name in names {
+            let subscriber = self.get_subscriber(session, name).await?;
+            let sub_cfg = &self
+                .get_subscriber_config(name)
+                .ok_or_else(|| ZenohInterfaceError::SubTopicNotFound(name.to_string()))?
+                .config;
+            topics.push(RecordedTopic {
+                name: name.to_string(),
+                encoding: sub_cfg
+                    .encoding
+                    .clone()
+                    .unwrap_or_else(|| DEFAULT_ENCODING.to_string()),
+                message_type: sub_cfg.message_type.clone(),
+                subscriber,
+            });
+        }
+        Ok(BagRecorder {
+            topics,
+            writer: BagWriter::create(path)?,
+        })
+    }
+
+    /// Open the bag at `path` for replay through the configured publishers named like the
+    /// recorded topics, at the original pace.
+    pub async fn get_bag_replayer(
+        &self,
+        session: &Session,
+        path: impl AsRef<Path>,
+    ) -> Result<BagReplayer, ZenohInterfaceError> {
+        let reader = BagReader::open(path)?;
+        let mut targets = BTreeMap::new();
+        for topic in reader.topics() {
+            let Some(pub_cfg) = self.get_publisher_config(topic) else {
+                continue;
+            };
+            let publisher = self.get_publisher(session, topic).await?;
+            let stamper = self.metadata_stamper(pub_cfg, &publisher);
+            targets.insert(topic.clone(), ReplayTarget { publisher, stamper });
+        }
+        Ok(BagReplayer {
+            reader,
+            targets,
+            mode: ReplayMode::Timed { rate: 1.0 },
+            position: 0,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn record(topic: &str, timestamp_ns: u64, payload: &[u8]) -> BagRecord {
+        BagRecord {
+            topic: topic.into(),
+            key: format!("key/{topic}"),
+            timestamp_ns,
+            encoding: "json".into(),
+            message_type: "my.Message".into(),
+            payload: payload.to_vec(),
+        }
+    }
+
+    #[test]
+    fn test_write_and_read_indexed_bag() {
+        let tmpdir = TempDir::new().unwrap();
+        let path = tmpdir.path().join("indexed.bag");
+        let records = [
+            record("camera", 100, b"frame-0"),
+            record("imu", 150, b""),
+            record("camera", 200, b"frame-1"),
+        ];
+        let mut writer = BagWriter::create(&path).unwrap();
+        for r in &records {
+            writer.write(r).unwrap();
+        }
+        writer.finish().unwrap();
+
+        let mut reader = BagReader::open(&path).unwrap();
+        assert_eq!(reader.len(), 3);
+        assert_eq!(reader.topics(), ["camera", "imu"]);
+        assert_eq!(reader.read(2).unwrap(), records[2]);
+        assert_eq!(reader.read(0).unwrap(), records[0]);
+        assert_eq!(reader.position_at(150), 1);
+        assert_eq!(reader.position_at(151), 2);
+        assert_eq!(reader.position_at(1_000), 3);
+        assert!(matches!(reader.read(3), Err(BagError::OutOfRange(3))));
+    }
+
+    #[test]
+    fn test_read_unfinished_bag() {
+        let tmpdir = TempDir::new().unwrap();
+        let path = tmpdir.path().join("unfinished.bag");
+        let mut writer = BagWriter::create(&path).unwrap();
+        writer.write(&record("camera", 1, b"a")).unwrap();
+        writer.write(&record("camera", 2, b"b")).unwrap();
+        writer.out.flush().unwrap();
+        drop(writer);
+        // Simulate a crash in the middle of the next record.
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(&[42, 0, 0, 0, 1, 2]).unwrap();
+
+        let mut reader = BagReader::open(&path).unwrap();
+        assert_eq!(reader.len(), 2);
+        assert_eq!(reader.read(1).unwrap().payload, b"b");
+    }
+
+    #[test]
+    fn test_reject_foreign_file() {
+        let tmpdir = TempDir::new().unwrap();
+        let path = tmpdir.path().join("foreign.bag");
+        std::fs::write(&path, b"definitely not a bag").unwrap();
+        assert!(matches!(BagReader::open(&path), Err(BagError::Format(_))));
+    }
+
+    #[test]
+    fn test_corrupt_lengths_do_not_allocate() {
+        let tmpdir = TempDir::new().unwrap();
+        let path = tmpdir.path().join("corrupt.bag");
+        let mut writer = BagWriter::create(&path).unwrap();
+        writer.write(&record("camera", 1, b"a")).unwrap();
+        writer.out.flush().unwrap();
+        let offset = writer.offset;
+        drop(writer);
+        // A record claiming close to 4 GiB followed by a few bytes.
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(&[0xff, 0xff, 0xff, 0xff, 1, 2, 3]).unwrap();
+
+        let mut reader = BagReader::open(&path).unwrap();
+        assert_eq!(reader.len(), 1);
+        reader.input.seek(SeekFrom::Start(offset)).unwrap();
+        assert!(matches!(
+            BagRecord::read_from(&mut reader.input),
+            Err(BagError::Io(e)) if e.kind() == io::ErrorKind::UnexpectedEof
+        ));
+
+        // An index trailer claiming more bytes than the file holds.
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(&u64::MAX.to_le_bytes()).unwrap();
+        file.write_all(INDEX_MAGIC).unwrap();
+        assert!(matches!(BagReader::open(&path), Err(BagError::Format(_))));
+    }
+
+    #[test]
+    fn test_reject_oversized_lengths() {
+        assert_eq!(length_prefix(7).unwrap(), 7u32.to_le_bytes());
+        let len = u32::MAX as usize + 1;
+        assert!(matches!(length_prefix(len), Err(BagError::TooLarge(l)) if l == len));
+    }
+}
@@ -0,0 +1,552 @@
+use crate::interfaces::zenoh::matching;
+use std::collections::hash_map::RandomState;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::hash::{BuildHasher, Hasher};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock, Weak};
+use std::time::{Duration, Instant};
+use tokio::runtime::Handle;
+use tokio::task::JoinHandle;
+use zenoh::bytes::{Encoding, ZBytes};
+use zenoh::pubsub::Publisher;
+use zenoh::sample::{Sample, SampleBuilder};
+use zenoh::Result as ZResult;
+
+/// Encoding schema marking a sample as one chunk of a larger payload.
+pub const CHUNK_SCHEMA: &str = "make87/chunk";
+
+/// Size of the header every chunk payload starts with: the source id, the transfer id, the
+/// chunk index and the number of chunks, little-endian.
+///
+/// The chunk data joined in order starts with the original encoding of the payload,
+/// prefixed with its length as a little-endian `u32`.
+const CHUNK_HEADER_LEN: usize = 24;
+
+/// Number of finished transfers remembered so that their late or duplicate chunks are
+/// dropped instead of starting a new transfer.
+const FINISHED_TRANSFERS: usize = 256;
+
+pub(crate) fn chunk_encoding() -> Encoding {
+    Encoding::APPLICATION_OCTET_STREAM.with_schema(CHUNK_SCHEMA)
+}
+
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+pub enum ChunkError {
+    #[error("Malformed chunk: {0}")]
+    Malformed(String),
+    #[error("Transfer {transfer_id} timed out with {received} of {count} chunks")]
+    Incomplete {
+        transfer_id: u64,
+        received: u32,
+        count: u32,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct ChunkHeader {
+    source: u64,
+    transfer_id: u64,
+    index: u32,
+    count: u32,
+}
+
+impl ChunkHeader {
+    fn write(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.source.to_le_bytes());
+        out.extend_from_slice(&self.transfer_id.to_le_bytes());
+        out.extend_from_slice(&self.index.to_le_bytes());
+        out.extend_from_slice(&self.count.to_le_bytes());
+    }
+
+    fn read(payload: &[u8]) -> Result<(Self, &[u8]), ChunkError> {
+        if payload.len() < CHUNK_HEADER_LEN {
+            return Err(ChunkError::Malformed(format!(
+                "{} bytes is shorter than the chunk header",
+                payload.len()
+            )));
+        }
+        let (header, data) = payload.split_at(CHUNK_HEADER_LEN);
+        let header = Self {
+            source: u64::from_le_bytes(header[..8].try_into().unwrap()),
+            transfer_id: u64::from_le_bytes(header[8..16].try_into().unwrap()),
+            index: u32::from_le_bytes(header[16..20].try_into().unwrap()),
+            count: u32::from_le_bytes(header[20..].try_into().unwrap()),
+        };
+        if header.index >= header.count {
+            return Err(ChunkError::Malformed(format!(
+                "chunk {} of transfer {} is out of its {} chunks",
+                header.index, header.transfer_id, header.count
+            )));
+        }
+        Ok((header, data))
+    }
+
+    fn key(&self) -> TransferKey {
+        (self.source, self.transfer_id)
+    }
+}
+
+/// Split the joined chunk data back into the original encoding and payload.
+fn read_encoded(data: &[u8]) -> Result<(Encoding, &[u8]), ChunkError> {
+    let truncated = || ChunkError::Malformed("transfer is shorter than its encoding".into());
+    let (len, rest) = data.split_at_checked(4).ok_or_else(truncated)?;
+    let len = u32::from_le_bytes(len.try_into().unwrap()) as usize;
+    let (encoding, payload) = rest.split_at_checked(len).ok_or_else(truncated)?;
+    let encoding = std::str::from_utf8(encoding)
+        .map_err(|e| ChunkError::Malformed(format!("invalid encoding: {e}")))?;
+    Ok((Encoding::from(encoding), payload))
+}
+
+/// Splits payloads larger than `chunk_size` into numbered chunks sharing a transfer id.
+///
+/// Every chunker tags its chunks with a random source id, so that subscribers keep
+/// transfers of publishers on the same key apart. Clones share the source id and the
+/// transfer id sequence.
+#[derive(Clone, Debug)]
+pub struct Chunker {
+    chunk_size: usize,
+    source: u64,
+    next_transfer: Arc<AtomicU64>,
+}
+
+impl Chunker {
+    pub fn new(chunk_size: usize) -> Self {
+        let random = || RandomState::new().build_hasher().finish();
+        Self {
+            chunk_size: chunk_size.max(1),
+            source: random(),
+            next_transfer: Arc::new(AtomicU64::new(random())),
+        }
+    }
+
+    pub fn chunk_size(&self) -> usize {
+        self.chunk_size
+    }
+
+    /// Split `payload` and its `encoding` into chunk payloads, or `None` if the payload fits
+    /// in a single sample.
+    pub(crate) fn split(&self, payload: &[u8], encoding: &Encoding) -> Option<Vec<Vec<u8>>> {
+        if payload.len() <= self.chunk_size {
+            return None;
+        }
+        let encoding = encoding.to_string();
+        let mut data = Vec::with_capacity(4 + encoding.len() + payload.len());
+        data.extend_from_slice(&(encoding.len() as u32).to_le_bytes());
+        data.extend_from_slice(encoding.as_bytes());
+        data.extend_from_slice(payload);
+
+        let transfer_id = self.next_transfer.fetch_add(1, Ordering::Relaxed);
+        let count = data.len().div_ceil(self.chunk_size) as u32;
+        let chunks = data
+            .chunks(self.chunk_size)
+            .enumerate()
+            .map(|(index, data)| {
+                let mut chunk = Vec::with_capacity(CHUNK_HEADER_LEN + data.len());
+                let header = ChunkHeader {
+                    source: self.source,
+                    transfer_id,
+                    index: index as u32,
+                    count,
+                };
+                header.write(&mut chunk);
+                chunk.extend_from_slice(data);
+                chunk
+            })
+            .collect();
+        Some(chunks)
+    }
+
+    /// Put `payload` on `publisher`, in chunks if it is larger than the chunk size.
+    ///
+    /// The attachment travels with the first chunk and ends up on the reassembled sample,
+    /// along with the publisher's encoding.
+    pub async fn put(
+        &self,
+        publisher: &Publisher<'static>,
+        payload: ZBytes,
+        attachment: Option<ZBytes>,
+    ) -> ZResult<()> {
+        let Some(chunks) = self.split(&payload.to_bytes(), publisher.encoding()) else {
+            return matching::put(publisher, payload, attachment).await;
+        };
+        let mut attachment = attachment;
+        for chunk in chunks {
+            let mut put = publisher.put(chunk).encoding(chunk_encoding());
+            if let Some(attachment) = attachment.take() {
+                put = put.attachment(attachment);
+            }
+            put.await?;
+        }
+        Ok(())
+    }
+}
+
+/// Put through `chunker` if there is one, as a single sample otherwise.
+pub(crate) async fn put(
+    publisher: &Publisher<'static>,
+    chunker: Option<&Chunker>,
+    payload: ZBytes,
+    attachment: Option<ZBytes>,
+) -> ZResult<()> {
+    match chunker {
+        Some(chunker) => chunker.put(publisher, payload, attachment).await,
+        None => matching::put(publisher, payload, attachment).await,
+    }
+}
+
+struct Transfer {
+    started: Instant,
+    count: u32,
+    first: Option<Sample>,
+    parts: BTreeMap<u32, Vec<u8>>,
+}
+
+/// `(source, transfer_id)` of a chunked transfer.
+type TransferKey = (u64, u64);
+
+#[derive(Default)]
+struct Transfers {
+    open: HashMap<TransferKey, Transfer>,
+    /// Recently completed or discarded transfers, oldest first.
+    finished: VecDeque<TransferKey>,
+}
+
+impl Transfers {
+    fn finish(&mut self, key: TransferKey) -> Option<Transfer> {
+        if self.finished.len() >= FINISHED_TRANSFERS {
+            self.finished.pop_front();
+        }
+        self.finished.push_back(key);
+        self.open.remove(&key)
+    }
+}
+
+type ErrorSink = Box<dyn Fn(ChunkError) + Send + Sync>;
+
+/// Reassembles chunked samples, passing every other sample through unchanged.
+///
+/// Transfers still missing chunks `timeout` after their first chunk arrived are discarded
+/// and reported to the error sink. Timeouts are checked as chunks arrive and, for a
+/// [`Reassembler::shared`] one, periodically in between.
+pub(crate) struct Reassembler {
+    timeout: Duration,
+    transfers: Mutex<Transfers>,
+    on_error: ErrorSink,
+    expiry: Option<LazyExpiry>,
+}
+
+/// Periodic expiry of a shared reassembler, started with its first chunk.
+struct LazyExpiry {
+    reassembler: Weak<Reassembler>,
+    runtime: Handle,
+    task: OnceLock<JoinHandle<()>>,
+}
+
+impl LazyExpiry {
+    /// Discard the transfers that time out, every `interval` until the reassembler is
+    /// dropped.
+    fn spawn(&self, interval: Duration) -> JoinHandle<()> {
+        let reassembler = self.reassembler.clone();
+        self.runtime.spawn(async move {
+            let mut ticker = tokio::time::interval(interval.max(Duration::from_millis(1)));
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                let Some(reassembler) = reassembler.upgrade() else {
+                    break;
+                };
+                reassembler.expire(Instant::now());
+            }
+        })
+    }
+}
+
+impl Reassembler {
+    pub(crate) fn new<F>(timeout: Duration, on_error: F) -> Self
+    where
+        F: Fn(ChunkError) + Send + Sync + 'static,
+    {
+        Self {
+            timeout,
+            transfers: Mutex::default(),
+            on_error: Box::new(on_error),
+            expiry: None,
+        }
+    }
+
+    /// A reassembler that also checks for timeouts every half `timeout`, from a task on the
+    /// calling tokio runtime. The task starts with the first chunk, so topics that are
+    /// never chunked cost nothing, and ends once the reassembler is dropped. Outside a
+    /// tokio runtime, timeouts are only checked as chunks arrive.
+    pub(crate) fn shared<F>(timeout: Duration, on_error: F) -> Arc<Self>
+    where
+        F: Fn(ChunkError) + Send + Sync + 'static,
+    {
+        let runtime = Handle::try_current().ok();
+        Arc::new_cyclic(|this| {
+            let mut reassembler = Self::new(timeout, on_error);
+            reassembler.expiry = runtime.map(|runtime| LazyExpiry {
+                reassembler: this.clone(),
+                runtime,
+                task: OnceLock::new(),
+            });
+            reassembler
+        })
+    }
+
+    fn start_expiry(&self) {
+        if let Some(expiry) = &self.expiry {
+            expiry.task.get_or_init(|| expiry.spawn(self.timeout / 2));
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Transfers> {
+        self.transfers.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn expire(&self, now: Instant) {
+        self.expire_locked(&mut self.lock(), now);
+    }
+
+    fn expire_locked(&self, transfers: &mut Transfers, now: Instant) {
+        let expired: Vec<TransferKey> = transfers
+            .open
+            .iter()
+            .filter(|(_, transfer)| now.duration_since(transfer.started) >= self.timeout)
+            .map(|(key, _)| *key)
+            .collect();
+        for key in expired {
+            let Some(transfer) = transfers.finish(key) else {
+                continue;
+            };
+            (self.on_error)(ChunkError::Incomplete {
+                transfer_id: key.1,
+                received: transfer.parts.len() as u32,
+                count: transfer.count,
+            });
+        }
+    }
+
+    /// The complete sample, if `sample` is not a chunk or is the last missing one.
+    pub(crate) fn accept(&self, sample: Sample) -> Option<Sample> {
+        self.accept_at(sample, Instant::now())
+    }
+
+    fn accept_at(&self, sample: Sample, now: Instant) -> Option<Sample> {
+        if *sample.encoding() != chunk_encoding() {
+            return Some(sample);
+        }
+        let payload = sample.payload().to_bytes();
+        let (header, data) = match ChunkHeader::read(&payload) {
+            Ok(chunk) => chunk,
+            Err(e) => {
+                (self.on_error)(e);
+                return None;
+            }
+        };
+
+        self.start_expiry();
+        let mut transfers = self.lock();
+        self.expire_locked(&mut transfers, now);
+        if transfers.finished.contains(&header.key()) {
+            return None;
+        }
+
+        let transfer = transfers
+            .open
+            .entry(header.key())
+            .or_insert_with(|| Transfer {
+                started: now,
+                count: header.count,
+                first: None,
+                parts: BTreeMap::new(),
+            });
+        if transfer.count != header.count {
+            (self.on_error)(ChunkError::Malformed(format!(
+                "transfer {} announced both {} and {} chunks",
+                header.transfer_id, transfer.count, header.count
+            )));
+            return None;
+        }
+        transfer.parts.insert(header.index, data.to_vec());
+        if header.index == 0 {
+            transfer.first = Some(sample.clone());
+        }
+        if transfer.parts.len() < transfer.count as usize {
+            return None;
+        }
+
+        let transfer = transfers.finish(header.key())?;
+        drop(transfers);
+        let data: Vec<u8> = transfer.parts.into_values().flatten().collect();
+        let (encoding, payload) = match read_encoded(&data) {
+            Ok(encoded) => encoded,
+            Err(e) => {
+                (self.on_error)(e);
+                return None;
+            }
+        };
+        let first = transfer.first.unwrap_or(sample);
+        let builder = SampleBuilder::try_from(first).ok()?;
+        Some(builder.payload(payload.to_vec()).encoding(encoding).into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use zenoh::key_expr::KeyExpr;
+
+    fn key(key: &'static str) -> KeyExpr<'static> {
+        KeyExpr::try_from(key).unwrap()
+    }
+
+    fn chunk_samples(chunker: &Chunker, payload: &[u8]) -> Vec<Sample> {
+        chunker
+            .split(payload, &Encoding::APPLICATION_JSON)
+            .unwrap()
+            .into_iter()
+            .map(|chunk| {
+                SampleBuilder::put(key("chunked/topic"), chunk)
+                    .encoding(chunk_encoding())
+                    .into()
+            })
+            .collect()
+    }
+
+    fn collecting_reassembler(timeout: Duration) -> (Reassembler, Arc<Mutex<Vec<ChunkError>>>) {
+        let errors = Arc::new(Mutex::new(Vec::new()));
+        let sink = errors.clone();
+        let reassembler = Reassembler::new(timeout, move |e| sink.lock().unwrap().push(e));
+        (reassembler, errors)
+    }
+
+    #[test]
+    fn test_small_payload_is_not_split() {
+        let chunker = Chunker::new(8);
+        let encoding = Encoding::default();
+        assert!(chunker.split(b"12345678", &encoding).is_none());
+        // The payload plus the 4 byte encoding length and the encoding itself.
+        let len = 9 + 4 + encoding.to_string().len();
+        let chunks = chunker.split(b"123456789", &encoding).unwrap();
+        assert_eq!(chunks.len(), len.div_ceil(8));
+    }
+
+    #[test]
+    fn test_reassemble_out_of_order() {
+        let chunker = Chunker::new(4);
+        let payload: Vec<u8> = (0..10).collect();
+        let mut chunks = chunk_samples(&chunker, &payload);
+        assert!(chunks.len() > 3);
+        chunks.swap(0, 2);
+
+        let (reassembler, errors) = collecting_reassembler(Duration::from_secs(1));
+        let last = chunks.pop().unwrap();
+        for chunk in chunks {
+            assert!(reassembler.accept(chunk).is_none());
+        }
+        let sample = reassembler.accept(last).unwrap();
+        assert_eq!(sample.payload().to_bytes(), payload);
+        assert_eq!(*sample.encoding(), Encoding::APPLICATION_JSON);
+        assert!(errors.lock().unwrap().is_empty());
+
+        let plain: Sample = SampleBuilder::put(key("plain/topic"), "hello").into();
+        let passed = reassembler.accept(plain).unwrap();
+        assert_eq!(passed.payload().to_bytes(), b"hello".as_slice());
+    }
+
+    #[test]
+    fn test_transfers_are_kept_apart_per_source() {
+        let (a, b) = (Chunker::new(4), Chunker::new(4));
+        b.next_transfer
+            .store(a.next_transfer.load(Ordering::Relaxed), Ordering::Relaxed);
+        let from_a = chunk_samples(&a, &[0xa; 10]);
+        let from_b = chunk_samples(&b, &[0xb; 10]);
+        let (reassembler, errors) = collecting_reassembler(Duration::from_secs(1));
+
+        let mut complete = Vec::new();
+        for (a, b) in from_a.into_iter().zip(from_b) {
+            complete.extend(reassembler.accept(a));
+            complete.extend(reassembler.accept(b));
+        }
+        let payloads: Vec<_> = complete.iter().map(|s| s.payload().to_bytes()).collect();
+        assert_eq!(payloads, [vec![0xa; 10], vec![0xb; 10]]);
+        assert!(errors.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_incomplete_transfer_times_out() {
+        let chunker = Chunker::new(4);
+        let first = chunk_samples(&chunker, &[0; 12]);
+        let second = chunk_samples(&chunker, &[1; 8]);
+        let (reassembler, errors) = collecting_reassembler(Duration::from_millis(100));
+
+        let start = Instant::now();
+        assert!(reassembler.accept_at(first[0].clone(), start).is_none());
+        assert!(reassembler.accept_at(first[1].clone(), start).is_none());
+        let later = start + Duration::from_millis(150);
+        let (last, rest) = second.split_last().unwrap();
+        for chunk in rest {
+            assert!(reassembler.accept_at(chunk.clone(), later).is_none());
+        }
+        let sample = reassembler.accept_at(last.clone(), later).unwrap();
+        assert_eq!(sample.payload().to_bytes(), vec![1; 8]);
+
+        let count = first.len() as u32;
+        assert!(matches!(
+            errors.lock().unwrap().as_slice(),
+            [ChunkError::Incomplete { received: 2, count: c, .. }] if *c == count
+        ));
+        // The discarded transfer's late chunks neither complete nor reopen it, so it is
+        // reported once.
+        let much_later = later + Duration::from_secs(1);
+        for chunk in &first[2..] {
+            assert!(reassembler.accept_at(chunk.clone(), later).is_none());
+        }
+        reassembler.expire(much_later);
+        assert_eq!(errors.lock().unwrap().len(), 1);
+        // Duplicates of a completed transfer are dropped as well.
+        assert!(reassembler.accept_at(last.clone(), much_later).is_none());
+        reassembler.expire(much_later + Duration::from_secs(1));
+        assert_eq!(errors.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_transfers_expire_without_new_chunks() {
+        let chunker = Chunker::new(4);
+        let chunks = chunk_samples(&chunker, &[0; 12]);
+        let errors = Arc::new(Mutex::new(Vec::new()));
+        let sink = errors.clone();
+        let reassembler = Reassembler::shared(Duration::from_millis(20), move |e| {
+            sink.lock().unwrap().push(e)
+        });
+        let started = |reassembler: &Reassembler| {
+            let expiry = reassembler.expiry.as_ref().unwrap();
+            expiry.task.get().is_some()
+        };
+
+        let whole: Sample = SampleBuilder::put(key("chunked/topic"), vec![0u8; 4]).into();
+        assert!(reassembler.accept(whole).is_some());
+        assert!(!started(&reassembler));
+        assert!(reassembler.accept(chunks[0].clone()).is_none());
+        assert!(started(&reassembler));
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert!(matches!(
+            errors.lock().unwrap().as_slice(),
+            [ChunkError::Incomplete { received: 1, .. }]
+        ));
+    }
+
+    #[test]
+    fn test_malformed_chunk() {
+        let (reassembler, errors) = collecting_reassembler(Duration::from_secs(1));
+        let sample: Sample = SampleBuilder::put(key("chunked/topic"), vec![0u8; 4])
+            .encoding(chunk_encoding())
+            .into();
+        assert!(reassembler.accept(sample).is_none());
+        assert!(matches!(
+            errors.lock().unwrap().as_slice(),
+            [ChunkError::Malformed(_)]
+        ));
+    }
+}
@@ -0,0 +1,93 @@
+use crate::interfaces::zenoh::ZenohSubscriberConfig;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+#[derive(Default)]
+struct DownsampleState {
+    seen: u64,
+    last_kept: Option<Instant>,
+}
+
+/// Decides which incoming samples a subscriber keeps, following the `keep_every_nth`,
+/// `max_rate_hz` and `min_interval_ms` settings of its [`ZenohSubscriberConfig`].
+///
+/// Runs in the zenoh callback, so dropped samples never take a slot in the handler channel.
+pub(crate) struct Downsampler {
+    every_nth: u64,
+    min_interval: Option<Duration>,
+    state: Mutex<DownsampleState>,
+}
+
+impl Downsampler {
+    /// `None` when the config keeps every sample.
+    pub(crate) fn new(config: &ZenohSubscriberConfig) -> Option<Self> {
+        config.is_downsampled().then(|| Self {
+            every_nth: config.keep_every_nth.unwrap_or(1).max(1),
+            min_interval: config.min_interval(),
+            state: Mutex::default(),
+        })
+    }
+
+    pub(crate) fn admit(&self) -> bool {
+        self.admit_at(Instant::now())
+    }
+
+    fn admit_at(&self, now: Instant) -> bool {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let index = state.seen;
+        state.seen += 1;
+        if !index.is_multiple_of(self.every_nth) {
+            return false;
+        }
+        if let (Some(min_interval), Some(last_kept)) = (self.min_interval, state.last_kept) {
+            if now.duration_since(last_kept) < min_interval {
+                return false;
+            }
+        }
+        state.last_kept = Some(now);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn downsampler(json: &str) -> Option<Downsampler> {
+        Downsampler::new(&serde_json::from_str(json).unwrap())
+    }
+
+    #[test]
+    fn test_keep_all_by_default() {
+        assert!(downsampler("{}").is_none());
+        assert!(downsampler(r#"{"keep_every_nth": 1}"#).is_none());
+    }
+
+    #[test]
+    fn test_keep_every_nth() {
+        let downsampler = downsampler(r#"{"keep_every_nth": 3}"#).unwrap();
+        let kept: Vec<_> = (0..7).filter(|_| downsampler.admit()).collect();
+        assert_eq!(kept, vec![0, 3, 6]);
+    }
+
+    #[test]
+    fn test_max_rate() {
+        let downsampler = downsampler(r#"{"max_rate_hz": 10.0}"#).unwrap();
+        let start = Instant::now();
+        let kept: Vec<_> = (0..10)
+            .filter(|i| downsampler.admit_at(start + Duration::from_millis(40 * i)))
+            .collect();
+        // One sample per 100 ms out of a 25 Hz stream.
+        assert_eq!(kept, vec![0, 3, 6, 9]);
+    }
+
+    #[test]
+    fn test_nth_then_min_interval() {
+        let downsampler = downsampler(r#"{"keep_every_nth": 2, "min_interval_ms": 50}"#).unwrap();
+        let start = Instant::now();
+        let kept: Vec<_> = (0..8)
+            .filter(|i| downsampler.admit_at(start + Duration::from_millis(20 * i)))
+            .collect();
+        assert_eq!(kept, vec![0, 4]);
+    }
+}
@@ -0,0 +1,474 @@
+use crate::interfaces::zenoh::model::HandlerChannel;
+use futures::Stream;
+use serde::Serialize;
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use zenoh::handlers::{
+    Callback, FifoChannel, FifoChannelHandler, IntoHandler, RingChannel, RingChannelHandler,
+};
+use zenoh::key_expr::KeyExpr;
+use zenoh::pubsub::Subscriber;
+use zenoh::query::{Query, Queryable};
+use zenoh::sample::Sample;
+use zenoh::Result as ZResult;
+
+enum ChannelHandler<T> {
+    Fifo(FifoChannelHandler<T>),
+    Ring(RingChannelHandler<T>),
+}
+
+impl<T> ChannelHandler<T> {
+    async fn recv_async(&self) -> ZResult<T> {
+        match self {
+            ChannelHandler::Fifo(handler) => handler.recv_async().await,
+            ChannelHandler::Ring(handler) => handler.recv_async().await,
+        }
+    }
+
+    fn try_recv(&self) -> ZResult<Option<T>> {
+        match self {
+            ChannelHandler::Fifo(handler) => handler.try_recv(),
+            ChannelHandler::Ring(handler) => handler.try_recv(),
+        }
+    }
+}
+
+/// Counters of a handler channel at one point in time.
+///
+/// A FIFO channel never drops: once it is full, the zenoh callback blocks until the
+/// consumer catches up, which shows as a `high_water_mark` equal to `capacity`. A ring
+/// channel overwrites its oldest item instead, counted in `dropped`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct HandlerStats {
+    pub capacity: u64,
+    pub received: u64,
+    pub dropped: u64,
+    pub queue_depth: u64,
+    pub high_water_mark: u64,
+    pub decode_failures: u64,
+    /// Chunked transfers discarded before all their chunks arrived.
+    pub incomplete_transfers: u64,
+}
+
+impl fmt::Display for HandlerStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "received={} dropped={} depth={}/{} high_water={} decode_failures={} \
+             incomplete_transfers={}",
+            self.received,
+            self.dropped,
+            self.queue_depth,
+            self.capacity,
+            self.high_water_mark,
+            self.decode_failures,
+            self.incomplete_transfers
+        )
+    }
+}
+
+#[derive(Default)]
+struct HandlerCounters {
+    capacity: u64,
+    overwrites: bool,
+    received: AtomicU64,
+    taken: AtomicU64,
+    dropped: AtomicU64,
+    high_water_mark: AtomicU64,
+    decode_failures: AtomicU64,
+    incomplete_transfers: AtomicU64,
+}
+
+impl HandlerCounters {
+    fn depth(&self) -> u64 {
+        let received = self.received.load(Ordering::Relaxed);
+        let gone = self.taken.load(Ordering::Relaxed) + self.dropped.load(Ordering::Relaxed);
+        received.saturating_sub(gone).min(self.capacity)
+    }
+
+    fn on_received(&self) {
+        if self.overwrites && self.depth() >= self.capacity {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+        self.received.fetch_add(1, Ordering::Relaxed);
+        self.high_water_mark
+            .fetch_max(self.depth(), Ordering::Relaxed);
+    }
+
+    fn on_taken<T>(&self, item: ZResult<T>) -> ZResult<T> {
+        if item.is_ok() {
+            self.taken.fetch_add(1, Ordering::Relaxed);
+        }
+        item
+    }
+
+    fn snapshot(&self) -> HandlerStats {
+        HandlerStats {
+            capacity: self.capacity,
+            received: self.received.load(Ordering::Relaxed),
+            dropped: self.dropped.load(Ordering::Relaxed),
+            queue_depth: self.depth(),
+            high_water_mark: self.high_water_mark.load(Ordering::Relaxed),
+            decode_failures: self.decode_failures.load(Ordering::Relaxed),
+            incomplete_transfers: self.incomplete_transfers.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Shared view of the counters of a subscriber or queryable channel, usable after the
+/// receiver itself has moved into a task.
+#[derive(Clone)]
+pub struct HandlerStatsHandle {
+    counters: Arc<HandlerCounters>,
+}
+
+impl HandlerStatsHandle {
+    pub fn stats(&self) -> HandlerStats {
+        self.counters.snapshot()
+    }
+
+    /// Print the stats to stderr every `interval`, prefixed with `label`, until the
+    /// channel's subscriber or queryable is dropped.
+    pub fn log_every(&self, label: &str, interval: Duration) -> JoinHandle<()> {
+        let counters = Arc::downgrade(&self.counters);
+        let label = label.to_string();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                let Some(counters) = counters.upgrade() else {
+                    break;
+                };
+                eprintln!("{label}: {}", counters.snapshot());
+            }
+        })
+    }
+
+    pub(crate) fn record_incomplete_transfer(&self) {
+        self.counters
+            .incomplete_transfers
+            .fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+type RecvFuture<T> = Pin<Box<dyn Future<Output = ZResult<T>> + Send>>;
+
+/// Receiving side of the channel selected by a [`HandlerChannel`].
+///
+/// Offers the same receive methods and a [`Stream`] implementation regardless of whether
+/// the deployment configured a FIFO or a ring channel. The stream ends once the channel
+/// is disconnected, i.e. when the owning subscriber or queryable is undeclared.
+pub struct HandlerReceiver<T> {
+    handler: Arc<ChannelHandler<T>>,
+    counters: Arc<HandlerCounters>,
+    // Only touched through `&mut self`; the mutex just keeps the receiver `Sync`.
+    next: Mutex<Option<RecvFuture<T>>>,
+}
+
+impl<T: Send + 'static> HandlerReceiver<T> {
+    /// Build the channel described by `handler`, returning the callback that feeds it.
+    pub(crate) fn new(handler: &HandlerChannel) -> (Callback<T>, Self) {
+        let (callback, handler, capacity, overwrites) = match handler {
+            HandlerChannel::Fifo { capacity } => {
+                let (callback, handler) = FifoChannel::new(*capacity as usize).into_handler();
+                (callback, ChannelHandler::Fifo(handler), *capacity, false)
+            }
+            HandlerChannel::Ring { capacity } => {
+                let (callback, handler) = RingChannel::new(*capacity as usize).into_handler();
+                (callback, ChannelHandler::Ring(handler), *capacity, true)
+            }
+        };
+        let counters = Arc::new(HandlerCounters {
+            capacity: capacity as u64,
+            overwrites,
+            ..Default::default()
+        });
+        let callback = {
+            let counters = counters.clone();
+            Callback::from(move |item| {
+                counters.on_received();
+                callback.call(item);
+            })
+        };
+        let receiver = Self {
+            handler: Arc::new(handler),
+            counters,
+            next: Mutex::new(None),
+        };
+        (callback, receiver)
+    }
+
+    /// Wait for the next item.
+    pub async fn recv_async(&self) -> ZResult<T> {
+        self.counters.on_taken(self.handler.recv_async().await)
+    }
+
+    /// Take the next item if one is ready, without waiting.
+    pub fn try_recv(&self) -> ZResult<Option<T>> {
+        match self.handler.try_recv() {
+            Ok(Some(item)) => self.counters.on_taken(Ok(item)).map(Some),
+            other => other,
+        }
+    }
+
+    pub fn stats(&self) -> HandlerStats {
+        self.counters.snapshot()
+    }
+
+    pub fn stats_handle(&self) -> HandlerStatsHandle {
+        HandlerStatsHandle {
+            counters: self.counters.clone(),
+        }
+    }
+
+    /// Count an item the consumer received but could not decode.
+    pub(crate) fn record_decode_failure(&self) {
+        self.counters
+            .decode_failures
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Take every item that is currently queued, without waiting.
+    pub fn drain(&self) -> impl Iterator<Item = T> + '_ {
+        std::iter::from_fn(move || self.try_recv().ok().flatten())
+    }
+}
+
+impl<T: Send + 'static> Stream for HandlerReceiver<T> {
+    type Item = T;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        let this = &mut *self;
+        let slot = this.next.get_mut().unwrap_or_else(|e| e.into_inner());
+        let next = slot.get_or_insert_with(|| {
+            let handler = this.handler.clone();
+            let counters = this.counters.clone();
+            Box::pin(async move { counters.on_taken(handler.recv_async().await) })
+        });
+        let item = futures::ready!(next.as_mut().poll(cx));
+        *slot = None;
+        Poll::Ready(item.ok())
+    }
+}
+
+/// A subscriber declared with the channel configured for its topic.
+///
+/// Replaces the former `Fifo`/`Ring` enum: FIFO and ring channels are received from through
+/// the same methods, so there is no variant to match on.
+pub struct ConfiguredSubscriber {
+    subscriber: Subscriber<()>,
+    receiver: HandlerReceiver<Sample>,
+}
+
+impl ConfiguredSubscriber {
+    pub(crate) fn new(subscriber: Subscriber<()>, receiver: HandlerReceiver<Sample>) -> Self {
+        Self {
+            subscriber,
+            receiver,
+        }
+    }
+
+    pub async fn recv_async(&self) -> ZResult<Sample> {
+        self.receiver.recv_async().await
+    }
+
+    pub fn try_recv(&self) -> ZResult<Option<Sample>> {
+        self.receiver.try_recv()
+    }
+
+    pub fn drain(&self) -> impl Iterator<Item = Sample> + '_ {
+        self.receiver.drain()
+    }
+
+    pub fn stats(&self) -> HandlerStats {
+        self.receiver.stats()
+    }
+
+    pub fn stats_handle(&self) -> HandlerStatsHandle {
+        self.receiver.stats_handle()
+    }
+
+    pub(crate) fn record_decode_failure(&self) {
+        self.receiver.record_decode_failure()
+    }
+
+    pub fn key_expr(&self) -> &KeyExpr<'static> {
+        self.subscriber.key_expr()
+    }
+
+    pub async fn undeclare(self) -> ZResult<()> {
+        self.subscriber.undeclare().await
+    }
+}
+
+impl Stream for ConfiguredSubscriber {
+    type Item = Sample;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Sample>> {
+        Pin::new(&mut self.receiver).poll_next(cx)
+    }
+}
+
+/// A queryable declared with the channel configured for its endpoint.
+///
+/// Like [`ConfiguredSubscriber`], this replaces the former `Fifo`/`Ring` enum.
+pub struct ConfiguredQueryable {
+    queryable: Queryable<()>,
+    receiver: HandlerReceiver<Query>,
+}
+
+impl ConfiguredQueryable {
+    pub(crate) fn new(queryable: Queryable<()>, receiver: HandlerReceiver<Query>) -> Self {
+        Self {
+            queryable,
+            receiver,
+        }
+    }
+
+    pub async fn recv_async(&self) -> ZResult<Query> {
+        self.receiver.recv_async().await
+    }
+
+    pub fn try_recv(&self) -> ZResult<Option<Query>> {
+        self.receiver.try_recv()
+    }
+
+    pub fn drain(&self) -> impl Iterator<Item = Query> + '_ {
+        self.receiver.drain()
+    }
+
+    pub fn stats(&self) -> HandlerStats {
+        self.receiver.stats()
+    }
+
+    pub fn stats_handle(&self) -> HandlerStatsHandle {
+        self.receiver.stats_handle()
+    }
+
+    pub(crate) fn record_decode_failure(&self) {
+        self.receiver.record_decode_failure()
+    }
+
+    pub fn key_expr(&self) -> &KeyExpr<'static> {
+        self.queryable.key_expr()
+    }
+
+    pub async fn undeclare(self) -> ZResult<()> {
+        self.queryable.undeclare().await
+    }
+}
+
+impl Stream for ConfiguredQueryable {
+    type Item = Query;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Query>> {
+        Pin::new(&mut self.receiver).poll_next(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::StreamExt;
+
+    #[tokio::test]
+    async fn test_fifo_receiver() {
+        let (callback, mut receiver) =
+            HandlerReceiver::<u32>::new(&HandlerChannel::Fifo { capacity: 4 });
+        assert_eq!(receiver.try_recv().unwrap(), None);
+
+        callback.call(1);
+        callback.call(2);
+        callback.call(3);
+        assert_eq!(receiver.recv_async().await.unwrap(), 1);
+        assert_eq!(receiver.next().await, Some(2));
+        assert_eq!(receiver.drain().collect::<Vec<_>>(), vec![3]);
+    }
+
+    #[tokio::test]
+    async fn test_ring_receiver_keeps_latest() {
+        let (callback, mut receiver) =
+            HandlerReceiver::<u32>::new(&HandlerChannel::Ring { capacity: 2 });
+        for i in 0..5 {
+            callback.call(i);
+        }
+        assert_eq!(receiver.next().await, Some(3));
+        assert_eq!(receiver.try_recv().unwrap(), Some(4));
+        assert_eq!(receiver.try_recv().unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_ring_stats_count_overwrites() {
+        let (callback, receiver) =
+            HandlerReceiver::<u32>::new(&HandlerChannel::Ring { capacity: 2 });
+        for i in 0..5 {
+            callback.call(i);
+        }
+        let stats = receiver.stats();
+        assert_eq!(stats.received, 5);
+        assert_eq!(stats.dropped, 3);
+        assert_eq!(stats.queue_depth, 2);
+        assert_eq!(stats.high_water_mark, 2);
+
+        assert_eq!(receiver.recv_async().await.unwrap(), 3);
+        assert_eq!(receiver.stats().queue_depth, 1);
+    }
+
+    #[tokio::test]
+    async fn test_fifo_stats_track_depth() {
+        let (callback, mut receiver) =
+            HandlerReceiver::<u32>::new(&HandlerChannel::Fifo { capacity: 4 });
+        let handle = receiver.stats_handle();
+        callback.call(1);
+        callback.call(2);
+        callback.call(3);
+        assert_eq!(receiver.next().await, Some(1));
+        assert_eq!(receiver.try_recv().unwrap(), Some(2));
+        receiver.record_decode_failure();
+
+        assert_eq!(
+            handle.stats(),
+            HandlerStats {
+                capacity: 4,
+                received: 3,
+                dropped: 0,
+                queue_depth: 1,
+                high_water_mark: 3,
+                decode_failures: 1,
+                incomplete_transfers: 0,
+            }
+        );
+        assert_eq!(
+            handle.stats().to_string(),
+            "received=3 dropped=0 depth=1/4 high_water=3 decode_failures=1 \
+             incomplete_transfers=0"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_stream_wakes_on_new_item() {
+        let (callback, receiver) =
+            HandlerReceiver::<u32>::new(&HandlerChannel::Fifo { capacity: 4 });
+        let task = tokio::spawn(async move { receiver.take(2).collect::<Vec<_>>().await });
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        callback.call(10);
+        callback.call(11);
+        assert_eq!(task.await.unwrap(), vec![10, 11]);
+    }
+
+    #[tokio::test]
+    async fn test_stream_ends_when_disconnected() {
+        let (callback, receiver) =
+            HandlerReceiver::<u32>::new(&HandlerChannel::Fifo { capacity: 4 });
+        callback.call(1);
+        drop(callback);
+        assert_eq!(receiver.collect::<Vec<_>>().await, vec![1]);
+    }
+}
@@ -0,0 +1,250 @@
+use crate::interfaces::zenoh::chunk::chunk_encoding;
+use crate::interfaces::zenoh::interface::decode_config;
+use crate::interfaces::zenoh::matching;
+use crate::interfaces::zenoh::{
+    Chunker, ZenohInterface, ZenohInterfaceError, ZenohPublisherConfig,
+};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use tokio::runtime::Handle;
+use zenoh::bytes::{Encoding, ZBytes};
+use zenoh::pubsub::Publisher;
+use zenoh::query::{ConsolidationMode, Query, QueryTarget, Queryable, ReplyKeyExpr};
+use zenoh::sample::Sample;
+use zenoh::{Result as ZResult, Session};
+
+/// Root of the key expressions publication caches answer history queries on.
+///
+/// The cache of a publisher on `<topic_key>` is queried on
+/// `make87/history/<topic_key>` and replies on `<topic_key>`, so history samples look
+/// the same as live ones to subscribers.
+pub const HISTORY_PREFIX: &str = "make87/history";
+
+pub(crate) fn history_key(topic_key: &str) -> String {
+    format!("{HISTORY_PREFIX}/{topic_key}")
+}
+
+type CachedSample = (ZBytes, Option<ZBytes>);
+
+/// Last samples put on a topic, bounded to `depth`.
+struct HistoryRing {
+    depth: usize,
+    samples: VecDeque<CachedSample>,
+    /// Splits replies like the publisher splits live samples, along with its encoding.
+    chunking: Option<(Chunker, Encoding)>,
+}
+
+impl HistoryRing {
+    fn push(&mut self, sample: CachedSample) {
+        if self.samples.len() >= self.depth {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(sample);
+    }
+
+    fn snapshot(&self) -> Vec<CachedSample> {
+        self.samples.iter().cloned().collect()
+    }
+}
+
+/// Keeps the last `history_depth` samples of a publisher and serves them to late-joining
+/// subscribers that request history.
+///
+/// Only samples put through [`PublicationCache::put`] or published by the owning
+/// [`TypedPublisher`](crate::interfaces::zenoh::TypedPublisher) are cached.
+pub struct PublicationCache {
+    queryable: Queryable<()>,
+    ring: Arc<Mutex<HistoryRing>>,
+}
+
+impl PublicationCache {
+    /// Declare a cache of up to `depth` samples for the topic on `topic_key`.
+    ///
+    /// History queries are answered from tasks on the calling tokio runtime, so that a slow
+    /// subscriber does not hold up zenoh's callback thread. Fails when called outside a
+    /// tokio runtime.
+    pub async fn new(session: &Session, topic_key: &str, depth: usize) -> ZResult<Self> {
+        let runtime = Handle::try_current()
+            .map_err(|e| format!("publication caches need a tokio runtime: {e}"))?;
+        let ring = Arc::new(Mutex::new(HistoryRing {
+            depth: depth.max(1),
+            samples: VecDeque::new(),
+            chunking: None,
+        }));
+        let topic_key = topic_key.to_string();
+        let queryable = session
+            .declare_queryable(history_key(&topic_key))
+            .callback({
+                let ring = ring.clone();
+                move |query| {
+                    // Snapshot now, so that the reply holds what was cached when asked.
+                    let (samples, chunking) = {
+                        let ring = ring.lock().unwrap_or_else(|e| e.into_inner());
+                        (ring.snapshot(), ring.chunking.clone())
+                    };
+                    let reply = reply_history(query, topic_key.clone(), samples, chunking);
+                    runtime.spawn(reply);
+                }
+            })
+            .await?;
+        Ok(Self { queryable, ring })
+    }
+
+    /// Put the sample on `publisher` and keep it for late joiners.
+    pub async fn put(
+        &self,
+        publisher: &Publisher<'static>,
+        payload: ZBytes,
+        attachment: Option<ZBytes>,
+    ) -> ZResult<()> {
+        self.push(payload.clone(), attachment.clone());
+        matching::put(publisher, payload, attachment).await
+    }
+
+    /// Reply with samples split by `chunker`, set for a publisher in `encoding`, or whole
+    /// without one.
+    pub(crate) fn chunk_replies(&self, chunker: Option<Chunker>, encoding: &Encoding) {
+        let mut ring = self.ring.lock().unwrap_or_else(|e| e.into_inner());
+        ring.chunking = chunker.map(|chunker| (chunker, encoding.clone()));
+    }
+
+    pub(crate) fn push(&self, payload: ZBytes, attachment: Option<ZBytes>) {
+        let mut ring = self.ring.lock().unwrap_or_else(|e| e.into_inner());
+        ring.push((payload, attachment));
+    }
+
+    /// Number of samples currently cached.
+    pub fn len(&self) -> usize {
+        let ring = self.ring.lock().unwrap_or_else(|e| e.into_inner());
+        ring.samples.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub async fn undeclare(self) -> ZResult<()> {
+        self.queryable.undeclare().await
+    }
+}
+
+/// Reply to `query` with `samples`, oldest first, in chunks if `chunking` splits them.
+/// The query is finalized once dropped.
+async fn reply_history(
+    query: Query,
+    topic_key: String,
+    samples: Vec<CachedSample>,
+    chunking: Option<(Chunker, Encoding)>,
+) {
+    for (payload, attachment) in samples {
+        let chunks = chunking
+            .as_ref()
+            .and_then(|(chunker, encoding)| chunker.split(&payload.to_bytes(), encoding));
+        let parts = match chunks {
+            Some(chunks) => chunks
+                .into_iter()
+                .map(|chunk| (ZBytes::from(chunk), Some(chunk_encoding())))
+                .collect(),
+            None => vec![(payload, None)],
+        };
+        // As on the live path, the attachment travels with the first chunk.
+        let mut attachment = attachment;
+        for (payload, encoding) in parts {
+            let mut reply = query.reply(&topic_key, payload);
+            if let Some(encoding) = encoding {
+                reply = reply.encoding(encoding);
+            }
+            if let Some(attachment) = attachment.take() {
+                reply = reply.attachment(attachment);
+            }
+            if let Err(e) = reply.await {
+                eprintln!("Failed to reply with cached sample on {topic_key}: {e}");
+                return;
+            }
+        }
+    }
+}
+
+/// Ask the publication caches of the topics on `topic_key` for their history and hand
+/// every cached sample to `deliver`, oldest first per publisher.
+///
+/// Replies arrive in the background and may interleave with live samples.
+pub(crate) async fn request_history<F>(
+    session: &Session,
+    topic_key: &str,
+    deliver: F,
+) -> ZResult<()>
+where
+    F: Fn(Sample) + Send + Sync + 'static,
+{
+    session
+        .get(history_key(topic_key))
+        .target(QueryTarget::All)
+        // Every cached sample replies on the same key, which consolidation would collapse.
+        .consolidation(ConsolidationMode::None)
+        .accept_replies(ReplyKeyExpr::Any)
+        .callback(move |reply| {
+            if let Ok(sample) = reply.into_result() {
+                deliver(sample)
+            }
+        })
+        .await
+}
+
+impl ZenohInterface {
+    /// Declare the publication cache of the publisher `name`, or `None` when its
+    /// `history_depth` is `0`.
+    ///
+    /// Like [`PublicationCache::new`], this must be called within a tokio runtime.
+    pub async fn get_publication_cache(
+        &self,
+        session: &Session,
+        name: &str,
+    ) -> Result<Option<PublicationCache>, ZenohInterfaceError> {
+        let pub_cfg = self
+            .get_publisher_config(name)
+            .ok_or_else(|| ZenohInterfaceError::PubTopicNotFound(name.to_string()))?;
+        let zenoh_config: ZenohPublisherConfig = decode_config(&pub_cfg.config)?;
+        if zenoh_config.history_depth == 0 {
+            return Ok(None);
+        }
+        let cache =
+            PublicationCache::new(session, &pub_cfg.topic_key, zenoh_config.history_depth).await?;
+        Ok(Some(cache))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_history_ring_keeps_last() {
+        let mut ring = HistoryRing {
+            depth: 2,
+            samples: VecDeque::new(),
+            chunking: None,
+        };
+        for i in 0..5u8 {
+            ring.push((ZBytes::from(vec![i]), None));
+        }
+        let kept: Vec<_> = ring.samples.iter().map(|(p, _)| p.to_bytes()[0]).collect();
+        assert_eq!(kept, vec![3, 4]);
+        assert_eq!(history_key("a/b"), "make87/history/a/b");
+    }
+
+    #[test]
+    fn test_cache_requires_tokio_runtime() {
+        use crate::testing::AppFixture;
+        use zenoh::Wait;
+
+        let config = AppFixture::new("history").zenoh_interface().zenoh_config();
+        let session = zenoh::open(config.unwrap()).wait().unwrap();
+        let result = futures::executor::block_on(PublicationCache::new(&session, "a/b", 1));
+        let Err(e) = result else {
+            panic!("Expected an error outside a tokio runtime");
+        };
+        assert!(e.to_string().contains("tokio runtime"), "{e}");
+        session.close().wait().unwrap();
+    }
+}
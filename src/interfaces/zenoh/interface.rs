@@ -1,20 +1,39 @@
 use crate::config::{load_config_from_default_env, ConfigError};
+use crate::encodings::{
+    BoxedEncoder, EncodeError, Encoder, EncoderRegistry, EncodingError, DEFAULT_ENCODING,
+};
+use crate::interfaces::zenoh::bag::BagError;
+use crate::interfaces::zenoh::chunk::{ChunkError, Reassembler};
+use crate::interfaces::zenoh::downsample::Downsampler;
+use crate::interfaces::zenoh::handler::{
+    ConfiguredQueryable, ConfiguredSubscriber, HandlerReceiver, HandlerStatsHandle,
+};
+use crate::interfaces::zenoh::history::request_history;
+use crate::interfaces::zenoh::liveliness::LivelinessTokens;
+use crate::interfaces::zenoh::metadata::MetadataStamper;
 use crate::interfaces::zenoh::model::{
-    HandlerChannel, ZenohPublisherConfig, ZenohQuerierConfig, ZenohQueryableConfig,
-    ZenohSubscriberConfig,
+    ListenPolicy, ZenohInterfaceConfig, ZenohPublisherConfig, ZenohQuerierConfig,
+    ZenohQueryableConfig, ZenohSubscriberConfig,
+};
+use crate::interfaces::zenoh::rpc::{RpcError, TypedQuerier, TypedQueryable};
+use crate::interfaces::zenoh::typed::{TypedPublisher, TypedSubscriber};
+use crate::interfaces::zenoh::validation::ValidationReport;
+use crate::models::{
+    ApplicationEnvConfig, ApplicationInfo, BoundSubscriber, InterfaceConfig,
+    ProviderEndpointConfig, PublisherTopicConfig,
 };
-use crate::models::{ApplicationEnvConfig, ProviderEndpointConfig, PublisherTopicConfig};
+use crate::runtime::RuntimeContext;
 use serde_json::Value;
-use std::collections::{BTreeMap, HashSet};
-use std::net::{Ipv4Addr, SocketAddrV4, TcpListener};
-use zenoh::handlers::{FifoChannel, FifoChannelHandler, RingChannel, RingChannelHandler};
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::{Arc, Mutex};
+use tokio::sync::OnceCell;
 use zenoh::pubsub::{Publisher, Subscriber};
 use zenoh::query::{Querier, Query, Queryable};
 use zenoh::sample::Sample;
 use zenoh::Error as ZError;
 use zenoh::{Config, Session};
 
-fn decode_config<T: serde::de::DeserializeOwned>(
+pub(crate) fn decode_config<T: serde::de::DeserializeOwned>(
     map: &BTreeMap<String, Value>,
 ) -> Result<T, ZenohInterfaceError> {
     Ok(serde_json::from_value(Value::Object(
@@ -32,6 +51,38 @@ pub enum ZenohInterfaceError {
     ReqEndpointNotFound(String),
     #[error("No provider endpoint found with name: {0}")]
     PrvEndpointNotFound(String),
+    #[error("Failed to encode message: {0}")]
+    Encode(EncodeError),
+    #[error("Failed to decode message: {0}")]
+    Decode(EncodeError),
+    #[error("Invalid encoding for '{name}': {source}")]
+    Encoding {
+        name: String,
+        #[source]
+        source: EncodingError,
+    },
+    #[error("Query on '{0}' timed out")]
+    Timeout(String),
+    #[error("No reply received for query on '{0}'")]
+    NoReply(String),
+    #[error("Provider replied with an error: {0}")]
+    Rpc(RpcError),
+    #[error("Received message type '{received}' on a subscriber expecting '{expected}'")]
+    UnexpectedMessageType { expected: String, received: String },
+    #[error("Invalid zenoh config:\n{0}")]
+    InvalidConfig(ValidationReport),
+    #[error("Shared memory error: {0}")]
+    Shm(String),
+    #[error("Deployed application id '{0}' cannot be used in a liveliness key expression")]
+    InvalidApplicationId(String),
+    #[error("Failed to open zenoh session listening on {endpoints:?}: {source}")]
+    Listen {
+        endpoints: Vec<String>,
+        #[source]
+        source: ZError,
+    },
+    #[error(transparent)]
+    Bag(#[from] BagError),
     #[error(transparent)]
     Config(#[from] ConfigError),
     #[error(transparent)]
@@ -40,19 +91,30 @@ pub enum ZenohInterfaceError {
     Zenoh(#[from] ZError),
 }
 
-pub enum ConfiguredSubscriber {
-    Fifo(Subscriber<FifoChannelHandler<Sample>>),
-    Ring(Subscriber<RingChannelHandler<Sample>>),
+/// Endpoints a session listens on instead of its configured ones, after
+/// [`ListenPolicy::Fallback`] retried on OS-assigned ports.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ListenFallback {
+    /// The configured endpoints, which could not be bound.
+    pub endpoints: Vec<String>,
+    /// The endpoints the session was opened with instead.
+    pub fallback: Vec<String>,
+    /// Why binding the configured endpoints failed.
+    pub reason: String,
 }
 
-pub enum ConfiguredQueryable {
-    Fifo(Queryable<FifoChannelHandler<Query>>),
-    Ring(Queryable<RingChannelHandler<Query>>),
+/// Whether `zenoh::open` failed because a listen endpoint could not be bound. Zenoh reports
+/// this only as text, e.g. "Can not create a new TCP listener bound to ...".
+fn is_listen_failure(error: &ZError) -> bool {
+    error.to_string().contains(" listener")
 }
 
 pub struct ZenohInterface {
     config: ApplicationEnvConfig,
     name: String,
+    session: Arc<OnceCell<Session>>,
+    listen_fallback: Arc<Mutex<Option<ListenFallback>>>,
+    liveliness: OnceCell<LivelinessTokens>,
 }
 
 impl ZenohInterface {
@@ -60,46 +122,234 @@ impl ZenohInterface {
         Self {
             config,
             name: name.to_string(),
+            session: Arc::default(),
+            listen_fallback: Arc::default(),
+            liveliness: OnceCell::new(),
         }
     }
 
     pub fn from_default_env(name: &str) -> Result<Self, ZenohInterfaceError> {
         let config = load_config_from_default_env()?;
-        Ok(Self {
-            config,
+        Ok(Self::new(config, name))
+    }
+
+    /// The interface `name` of the same application, sharing this interface's session.
+    pub fn share_session(&self, name: &str) -> Self {
+        Self {
+            config: self.config.clone(),
             name: name.to_string(),
-        })
+            session: self.session.clone(),
+            listen_fallback: self.listen_fallback.clone(),
+            liveliness: OnceCell::new(),
+        }
     }
 
-    pub fn zenoh_config(&self) -> Result<Config, ZenohInterfaceError> {
-        let mut cfg = Config::default();
-        if !is_port_in_use(7447) {
-            let listen_endpoints = vec!["tcp/0.0.0.0:7447"];
-            let listen_json = serde_json::to_string(&listen_endpoints)?;
-            cfg.insert_json5("listen/endpoints", &listen_json)?;
+    pub fn interface_config(&self) -> Option<&InterfaceConfig> {
+        self.config.interfaces.get(&self.name)
+    }
+
+    pub fn application_info(&self) -> &ApplicationInfo {
+        &self.config.application_info
+    }
+
+    /// Interface-level zenoh settings, taken from the keys of this interface's config.
+    pub fn zenoh_interface_config(&self) -> Result<ZenohInterfaceConfig, ZenohInterfaceError> {
+        match self.config.interfaces.get(&self.name) {
+            Some(iface) => decode_config(&iface.config),
+            None => Ok(ZenohInterfaceConfig::default()),
         }
+    }
 
-        let endpoints_set: HashSet<_> = self
-            .config
-            .interfaces
-            .get(&self.name)
-            .map(|iface| iface.subscribers.values().map(|s| &s.access_point))
-            .into_iter()
-            .flatten()
-            .map(|ap| format!("tcp/{}:{}", ap.vpn_ip, ap.vpn_port))
+    pub fn zenoh_config(&self) -> Result<Config, ZenohInterfaceError> {
+        let settings = self.zenoh_interface_config()?;
+        self.zenoh_config_with_listeners(&settings, &settings.listen_endpoints())
+    }
+
+    /// Locators of every bound subscriber, requester and client access point on this
+    /// interface, chosen by the configured [`ConnectPolicy`](super::ConnectPolicy), sorted
+    /// and de-duplicated.
+    ///
+    /// Explicit `connect_endpoints` in the interface config replace the access points.
+    pub fn connect_endpoints(&self) -> Result<Vec<String>, ZenohInterfaceError> {
+        let Some(iface) = self.config.interfaces.get(&self.name) else {
+            return Ok(Vec::new());
+        };
+        let settings = self.zenoh_interface_config()?;
+        if let Some(endpoints) = settings.connect_endpoints {
+            return Ok(endpoints);
+        }
+        let policy = settings.connect_policy;
+        let endpoints: BTreeSet<_> = iface
+            .subscribers
+            .values()
+            .map(|s| &s.access_point)
+            .chain(iface.requesters.values().map(|r| &r.access_point))
+            .chain(iface.clients.values().map(|c| &c.access_point))
+            .map(|ap| policy.locator(ap))
             .collect();
+        Ok(endpoints.into_iter().collect())
+    }
 
-        let mut endpoints: Vec<_> = endpoints_set.into_iter().collect();
-        endpoints.sort();
+    fn zenoh_config_with_listeners(
+        &self,
+        settings: &ZenohInterfaceConfig,
+        listen_endpoints: &[String],
+    ) -> Result<Config, ZenohInterfaceError> {
+        let mut cfg = Config::default();
+        let listen_json = serde_json::to_string(listen_endpoints)?;
+        cfg.insert_json5("listen/endpoints", &listen_json)?;
+        cfg.insert_json5("listen/exit_on_failure", "true")?;
+        if !settings.multicast_scouting {
+            cfg.insert_json5("scouting/multicast/enabled", "false")?;
+        }
+        if settings.shm.is_some() {
+            cfg.insert_json5("transport/shared_memory/enabled", "true")?;
+        }
 
-        let endpoints_json = serde_json::to_string(&endpoints)?;
+        let endpoints_json = serde_json::to_string(&self.connect_endpoints()?)?;
         cfg.insert_json5("connect/endpoints", &endpoints_json)?;
         Ok(cfg)
     }
 
+    /// Open a session listening on the configured endpoints.
+    ///
+    /// Fails with [`ZenohInterfaceError::InvalidConfig`] before opening anything if
+    /// [`ZenohInterface::validate`] finds an issue in the interface's settings. If the
+    /// endpoints cannot be bound, the configured [`ListenPolicy`] decides whether this fails
+    /// with [`ZenohInterfaceError::Listen`] or retries on OS-assigned ports, as then reported
+    /// by [`ZenohInterface::listen_fallback`]. Any other failure to open is returned as is.
     pub async fn get_session(&self) -> Result<Session, ZenohInterfaceError> {
-        let cfg = self.zenoh_config()?;
-        zenoh::open(cfg).await.map_err(Into::into)
+        self.validate()?;
+        let settings = self.zenoh_interface_config()?;
+        let endpoints = settings.listen_endpoints();
+        let cfg = self.zenoh_config_with_listeners(&settings, &endpoints)?;
+        let source = match zenoh::open(cfg).await {
+            Ok(session) => {
+                self.set_listen_fallback(None);
+                return Ok(session);
+            }
+            Err(source) if is_listen_failure(&source) => source,
+            Err(source) => return Err(source.into()),
+        };
+        if settings.listen_policy == ListenPolicy::Strict {
+            return Err(ZenohInterfaceError::Listen { endpoints, source });
+        }
+
+        let fallback = settings.fallback_listen_endpoints();
+        let cfg = self.zenoh_config_with_listeners(&settings, &fallback)?;
+        let session = zenoh::open(cfg)
+            .await
+            .map_err(|source| ZenohInterfaceError::Listen {
+                endpoints: fallback.clone(),
+                source,
+            })?;
+        self.set_listen_fallback(Some(ListenFallback {
+            endpoints,
+            fallback,
+            reason: source.to_string(),
+        }));
+        Ok(session)
+    }
+
+    /// How the session last opened by [`ZenohInterface::get_session`], by this interface or
+    /// one sharing its session, fell back from its configured listen endpoints, if it did.
+    pub fn listen_fallback(&self) -> Option<ListenFallback> {
+        let fallback = self
+            .listen_fallback
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        fallback.clone()
+    }
+
+    fn set_listen_fallback(&self, fallback: Option<ListenFallback>) {
+        *self
+            .listen_fallback
+            .lock()
+            .unwrap_or_else(|e| e.into_inner()) = fallback;
+    }
+
+    /// The session owned by this interface, opened with [`ZenohInterface::get_session`] on
+    /// first use.
+    ///
+    /// Interfaces created with [`ZenohInterface::share_session`] use the same session,
+    /// opened with the settings of whichever interface uses it first. The session closes
+    /// once the last interface sharing it is dropped, or on [`ZenohInterface::close`].
+    ///
+    /// On first use, each interface also declares its liveliness tokens on the session, see
+    /// [`ZenohInterface::declare_liveliness`], unless the application has no
+    /// `deployed_application_id`.
+    pub async fn session(&self) -> Result<&Session, ZenohInterfaceError> {
+        let session = self.session.get_or_try_init(|| self.get_session()).await?;
+        if !self.application_info().deployed_application_id.is_empty() {
+            self.liveliness
+                .get_or_try_init(|| self.declare_liveliness(session))
+                .await?;
+        }
+        Ok(session)
+    }
+
+    /// Close the owned session if it was opened, undeclaring everything declared on it.
+    pub async fn close(&self) -> Result<(), ZenohInterfaceError> {
+        if let Some(session) = self.session.get() {
+            session.close().await?;
+        }
+        Ok(())
+    }
+
+    /// Close the owned session when `context` shuts down, if it was opened by then.
+    pub fn close_on_shutdown(&self, context: &RuntimeContext) {
+        let session = self.session.clone();
+        context.on_shutdown(move || async move {
+            let Some(session) = session.get() else {
+                return;
+            };
+            if let Err(e) = session.close().await {
+                eprintln!("Failed to close zenoh session: {e}");
+            }
+        });
+    }
+
+    /// Declare the publisher `name` on the owned session.
+    ///
+    /// Like [`ZenohInterface::get_publisher`], the raw publisher attaches no metadata.
+    pub async fn publisher(&self, name: &str) -> Result<Publisher<'static>, ZenohInterfaceError> {
+        self.get_publisher(self.session().await?, name).await
+    }
+
+    /// Declare the subscriber `name` on the owned session.
+    pub async fn subscriber(
+        &self,
+        name: &str,
+    ) -> Result<ConfiguredSubscriber, ZenohInterfaceError> {
+        self.get_subscriber(self.session().await?, name).await
+    }
+
+    pub async fn typed_publisher<T, E: Encoder<T>>(
+        &self,
+        name: &str,
+        encoder: E,
+    ) -> Result<TypedPublisher<T, E>, ZenohInterfaceError> {
+        self.get_typed_publisher(self.session().await?, name, encoder)
+            .await
+    }
+
+    pub async fn typed_subscriber<T, E: Encoder<T>>(
+        &self,
+        name: &str,
+        encoder: E,
+    ) -> Result<TypedSubscriber<T, E>, ZenohInterfaceError> {
+        self.get_typed_subscriber(self.session().await?, name, encoder)
+            .await
+    }
+
+    /// Declare the querier `name` on the owned session.
+    pub async fn querier(&self, name: &str) -> Result<Querier<'static>, ZenohInterfaceError> {
+        self.get_querier(self.session().await?, name).await
+    }
+
+    /// Declare the queryable `name` on the owned session.
+    pub async fn queryable(&self, name: &str) -> Result<ConfiguredQueryable, ZenohInterfaceError> {
+        self.get_queryable(self.session().await?, name).await
     }
 
     pub fn get_publisher_config(&self, topic_name: &str) -> Option<&PublisherTopicConfig> {
@@ -140,11 +390,18 @@ impl ZenohInterface {
             .get(endpoint_name)
     }
 
+    /// Declare the publisher `name` with its configured QoS.
+    ///
+    /// Samples put on the raw publisher carry no [`MessageMetadata`](super::MessageMetadata),
+    /// and skip the chunking, matching buffer and history of typed publishers. Use
+    /// [`ZenohInterface::get_typed_publisher`] for those, or attach metadata by hand from
+    /// [`ZenohInterface::get_metadata_stamper`].
     pub async fn get_publisher(
         &self,
         session: &Session,
         name: &str,
     ) -> Result<Publisher<'static>, ZenohInterfaceError> {
+        self.validate()?;
         let pub_cfg = self
             .get_publisher_config(name)
             .ok_or_else(|| ZenohInterfaceError::PubTopicNotFound(name.to_string()))?;
@@ -168,22 +425,177 @@ impl ZenohInterface {
             .get_subscriber_config(name)
             .ok_or_else(|| ZenohInterfaceError::SubTopicNotFound(name.to_string()))?;
         let zenoh_config: ZenohSubscriberConfig = decode_config(&sub_cfg.config.config)?;
-        match &zenoh_config.handler {
-            HandlerChannel::Fifo { capacity } => {
-                let subscriber = session
-                    .declare_subscriber(sub_cfg.config.topic_key.clone())
-                    .with(FifoChannel::new(*capacity as usize))
-                    .await?;
-                Ok(ConfiguredSubscriber::Fifo(subscriber))
+        let (callback, receiver) = HandlerReceiver::new(&zenoh_config.handler);
+        let stats = Some(receiver.stats_handle());
+        let subscriber = self
+            .declare_subscriber(session, sub_cfg, &zenoh_config, stats, move |sample| {
+                callback.call(sample)
+            })
+            .await?;
+        Ok(ConfiguredSubscriber::new(subscriber, receiver))
+    }
+
+    /// Declare the subscriber of `sub_cfg`, applying its reassembly, downsampling and
+    /// history settings in front of `deliver`.
+    ///
+    /// Discarded chunked transfers are counted in `stats`, if given.
+    async fn declare_subscriber<F>(
+        &self,
+        session: &Session,
+        sub_cfg: &BoundSubscriber,
+        zenoh_config: &ZenohSubscriberConfig,
+        stats: Option<HandlerStatsHandle>,
+        deliver: F,
+    ) -> Result<Subscriber<()>, ZenohInterfaceError>
+    where
+        F: Fn(Sample) + Send + Sync + 'static,
+    {
+        self.validate()?;
+        let topic_key = &sub_cfg.config.topic_key;
+        let chunk_timeout = zenoh_config.chunk_timeout();
+        let reassembler = Reassembler::shared(chunk_timeout, {
+            let topic_key = topic_key.clone();
+            move |e| {
+                if let (ChunkError::Incomplete { .. }, Some(stats)) = (&e, &stats) {
+                    stats.record_incomplete_transfer();
+                }
+                eprintln!("Dropped chunked sample on {topic_key}: {e}");
             }
-            HandlerChannel::Ring { capacity } => {
-                let subscriber = session
-                    .declare_subscriber(sub_cfg.config.topic_key.clone())
-                    .with(RingChannel::new(*capacity as usize))
-                    .await?;
-                Ok(ConfiguredSubscriber::Ring(subscriber))
+        });
+        let downsampler = Downsampler::new(zenoh_config);
+        let deliver = Arc::new(move |sample| {
+            let Some(sample) = reassembler.accept(sample) else {
+                return;
+            };
+            if downsampler.as_ref().is_none_or(Downsampler::admit) {
+                deliver(sample)
             }
+        });
+        let subscriber = session
+            .declare_subscriber(topic_key.clone())
+            .callback({
+                let deliver = deliver.clone();
+                move |sample| deliver(sample)
+            })
+            .await?;
+        // Declared after the subscriber so that nothing published in between is lost.
+        if zenoh_config.request_history {
+            request_history(session, topic_key, move |sample| deliver(sample)).await?;
         }
+        Ok(subscriber)
+    }
+
+    pub async fn get_typed_publisher<T, E: Encoder<T>>(
+        &self,
+        session: &Session,
+        name: &str,
+        encoder: E,
+    ) -> Result<TypedPublisher<T, E>, ZenohInterfaceError> {
+        let pub_cfg = self
+            .get_publisher_config(name)
+            .ok_or_else(|| ZenohInterfaceError::PubTopicNotFound(name.to_string()))?;
+        let zenoh_config: ZenohPublisherConfig = decode_config(&pub_cfg.config)?;
+        let publisher = self.get_publisher(session, name).await?;
+        let stamper = self.metadata_stamper(pub_cfg, &publisher);
+        let mut typed = TypedPublisher::new(publisher, encoder)
+            .with_metadata(stamper)
+            .with_chunking(zenoh_config.chunk_size)
+            .buffer_until_matched(zenoh_config.buffer_until_matched)
+            .await?;
+        if let Some(cache) = self.get_publication_cache(session, name).await? {
+            typed = typed.with_history(cache);
+        }
+        Ok(typed)
+    }
+
+    /// Metadata stamper for samples put on `publisher`, declared for the publisher `name`.
+    ///
+    /// ```ignore
+    /// let publisher = iface.get_publisher(&session, "OUT").await?;
+    /// let stamper = iface.get_metadata_stamper("OUT", &publisher)?;
+    /// publisher
+    ///     .put(payload)
+    ///     .attachment(stamper.next().to_attachment()?)
+    ///     .await?;
+    /// ```
+    pub fn get_metadata_stamper(
+        &self,
+        name: &str,
+        publisher: &Publisher<'static>,
+    ) -> Result<MetadataStamper, ZenohInterfaceError> {
+        let pub_cfg = self
+            .get_publisher_config(name)
+            .ok_or_else(|| ZenohInterfaceError::PubTopicNotFound(name.to_string()))?;
+        Ok(self.metadata_stamper(pub_cfg, publisher))
+    }
+
+    /// Metadata stamper for samples sent by `publisher` on the topic configured by `pub_cfg`.
+    pub(crate) fn metadata_stamper(
+        &self,
+        pub_cfg: &PublisherTopicConfig,
+        publisher: &Publisher<'static>,
+    ) -> MetadataStamper {
+        let publisher_id = publisher.id();
+        MetadataStamper::new(
+            &pub_cfg.message_type,
+            pub_cfg.encoding.as_deref().unwrap_or(DEFAULT_ENCODING),
+            &self.config.application_info.deployed_application_id,
+            &format!("{}:{}", publisher_id.zid(), publisher_id.eid()),
+        )
+    }
+
+    pub async fn get_typed_subscriber<T, E: Encoder<T>>(
+        &self,
+        session: &Session,
+        name: &str,
+        encoder: E,
+    ) -> Result<TypedSubscriber<T, E>, ZenohInterfaceError> {
+        let sub_cfg = self
+            .get_subscriber_config(name)
+            .ok_or_else(|| ZenohInterfaceError::SubTopicNotFound(name.to_string()))?;
+        let subscriber = self.get_subscriber(session, name).await?;
+        Ok(TypedSubscriber::new(subscriber, encoder)
+            .with_message_type(&sub_cfg.config.message_type))
+    }
+
+    /// Declare a typed publisher whose encoder is picked from `registry` by the topic's
+    /// configured `encoding`.
+    pub async fn get_typed_publisher_from_registry<T>(
+        &self,
+        session: &Session,
+        name: &str,
+        registry: &EncoderRegistry<T>,
+    ) -> Result<TypedPublisher<T, BoxedEncoder<T>>, ZenohInterfaceError> {
+        let pub_cfg = self
+            .get_publisher_config(name)
+            .ok_or_else(|| ZenohInterfaceError::PubTopicNotFound(name.to_string()))?;
+        let encoder = resolve_encoder(
+            registry,
+            name,
+            &pub_cfg.message_type,
+            pub_cfg.encoding.as_deref(),
+        )?;
+        self.get_typed_publisher(session, name, encoder).await
+    }
+
+    /// Declare a typed subscriber whose encoder is picked from `registry` by the topic's
+    /// configured `encoding`.
+    pub async fn get_typed_subscriber_from_registry<T>(
+        &self,
+        session: &Session,
+        name: &str,
+        registry: &EncoderRegistry<T>,
+    ) -> Result<TypedSubscriber<T, BoxedEncoder<T>>, ZenohInterfaceError> {
+        let sub_cfg = self
+            .get_subscriber_config(name)
+            .ok_or_else(|| ZenohInterfaceError::SubTopicNotFound(name.to_string()))?;
+        let encoder = resolve_encoder(
+            registry,
+            name,
+            &sub_cfg.config.message_type,
+            sub_cfg.config.encoding.as_deref(),
+        )?;
+        self.get_typed_subscriber(session, name, encoder).await
     }
 
     pub async fn get_subscriber_callback(
@@ -195,11 +607,9 @@ impl ZenohInterface {
         let sub_cfg = self
             .get_subscriber_config(name)
             .ok_or_else(|| ZenohInterfaceError::SubTopicNotFound(name.to_string()))?;
-        let subscriber = session
-            .declare_subscriber(sub_cfg.config.topic_key.clone())
-            .callback(handler)
-            .await?;
-        Ok(subscriber)
+        let zenoh_config: ZenohSubscriberConfig = decode_config(&sub_cfg.config.config)?;
+        self.declare_subscriber(session, sub_cfg, &zenoh_config, None, handler)
+            .await
     }
 
     pub async fn get_subscriber_callback_mut(
@@ -211,11 +621,13 @@ impl ZenohInterface {
         let sub_cfg = self
             .get_subscriber_config(name)
             .ok_or_else(|| ZenohInterfaceError::SubTopicNotFound(name.to_string()))?;
-        let subscriber = session
-            .declare_subscriber(sub_cfg.config.topic_key.clone())
-            .callback_mut(handler)
-            .await?;
-        Ok(subscriber)
+        let zenoh_config: ZenohSubscriberConfig = decode_config(&sub_cfg.config.config)?;
+        let handler = Mutex::new(handler);
+        self.declare_subscriber(session, sub_cfg, &zenoh_config, None, move |sample| {
+            let mut handler = handler.lock().unwrap_or_else(|e| e.into_inner());
+            handler(sample)
+        })
+        .await
     }
 
     pub async fn get_querier(
@@ -223,6 +635,7 @@ impl ZenohInterface {
         session: &Session,
         name: &str,
     ) -> Result<Querier<'static>, ZenohInterfaceError> {
+        self.validate()?;
         let req_cfg = self
             .get_requester_config(name)
             .ok_or_else(|| ZenohInterfaceError::ReqEndpointNotFound(name.to_string()))?;
@@ -232,37 +645,92 @@ impl ZenohInterface {
             .congestion_control(zenoh_config.congestion_control.to_zenoh())
             .priority(zenoh_config.priority.to_zenoh())
             .express(zenoh_config.express)
+            .timeout(zenoh_config.timeout())
+            .target(zenoh_config.target.to_zenoh())
+            .consolidation(zenoh_config.consolidation.to_zenoh())
+            .accept_replies(zenoh_config.accept_replies.to_zenoh())
             .await?;
         Ok(querier)
     }
 
+    /// Declare a typed querier. The request encoder is picked from `requests` by the
+    /// endpoint's `requester_message_type` and `encoding`, the response encoder from
+    /// `responses` by its `provider_message_type`.
+    pub async fn get_typed_querier<Req, Resp>(
+        &self,
+        session: &Session,
+        name: &str,
+        requests: &EncoderRegistry<Req>,
+        responses: &EncoderRegistry<Resp>,
+    ) -> Result<TypedQuerier<Req, Resp>, ZenohInterfaceError> {
+        let req_cfg = self
+            .get_requester_config(name)
+            .ok_or_else(|| ZenohInterfaceError::ReqEndpointNotFound(name.to_string()))?;
+        let encoding = req_cfg.config.encoding.as_deref();
+        let request_encoder = resolve_encoder(
+            requests,
+            name,
+            &req_cfg.config.requester_message_type,
+            encoding,
+        )?;
+        let response_encoder = resolve_encoder(
+            responses,
+            name,
+            &req_cfg.config.provider_message_type,
+            encoding,
+        )?;
+        let zenoh_config: ZenohQuerierConfig = decode_config(&req_cfg.config.config)?;
+        let querier = self.get_querier(session, name).await?;
+        Ok(TypedQuerier::new(
+            session,
+            querier,
+            zenoh_config,
+            request_encoder,
+            response_encoder,
+        ))
+    }
+
     pub async fn get_queryable(
         &self,
         session: &Session,
         name: &str,
     ) -> Result<ConfiguredQueryable, ZenohInterfaceError> {
+        self.validate()?;
         let prv_cfg = self
             .get_provider_config(name)
             .ok_or_else(|| ZenohInterfaceError::PrvEndpointNotFound(name.to_string()))?;
         let zenoh_config: ZenohQueryableConfig = decode_config(&prv_cfg.config)?;
-        match &zenoh_config.handler {
-            HandlerChannel::Fifo { capacity } => {
-                let queryable = session
-                    .declare_queryable(prv_cfg.endpoint_key.clone())
-                    .with(FifoChannel::new(*capacity as usize))
-                    .await?;
-                Ok(ConfiguredQueryable::Fifo(queryable))
-            }
-            HandlerChannel::Ring { capacity } => {
-                let queryable = session
-                    .declare_queryable(prv_cfg.endpoint_key.clone())
-                    .with(RingChannel::new(*capacity as usize))
-                    .await?;
-                Ok(ConfiguredQueryable::Ring(queryable))
-            }
-        }
+        let (callback, receiver) = HandlerReceiver::new(&zenoh_config.handler);
+        let queryable = session
+            .declare_queryable(prv_cfg.endpoint_key.clone())
+            .callback(move |query| callback.call(query))
+            .await?;
+        Ok(ConfiguredQueryable::new(queryable, receiver))
     }
 
+    /// Declare a typed queryable, picking encoders like [`ZenohInterface::get_typed_querier`].
+    pub async fn get_typed_queryable<Req, Resp>(
+        &self,
+        session: &Session,
+        name: &str,
+        requests: &EncoderRegistry<Req>,
+        responses: &EncoderRegistry<Resp>,
+    ) -> Result<TypedQueryable<Req, Resp>, ZenohInterfaceError> {
+        let prv_cfg = self
+            .get_provider_config(name)
+            .ok_or_else(|| ZenohInterfaceError::PrvEndpointNotFound(name.to_string()))?;
+        let encoding = prv_cfg.encoding.as_deref();
+        let request_encoder =
+            resolve_encoder(requests, name, &prv_cfg.requester_message_type, encoding)?;
+        let response_encoder =
+            resolve_encoder(responses, name, &prv_cfg.provider_message_type, encoding)?;
+        let queryable = self.get_queryable(session, name).await?;
+        Ok(TypedQueryable::new(
+            queryable,
+            request_encoder,
+            response_encoder,
+        ))
+    }
 
     pub async fn get_queryable_callback(
         &self,
@@ -270,6 +738,7 @@ impl ZenohInterface {
         name: &str,
         handler: Box<dyn Fn(Query) + Send + Sync + 'static>,
     ) -> Result<Queryable<()>, ZenohInterfaceError> {
+        self.validate()?;
         let prv_cfg = self
             .get_provider_config(name)
             .ok_or_else(|| ZenohInterfaceError::PrvEndpointNotFound(name.to_string()))?;
@@ -286,6 +755,7 @@ impl ZenohInterface {
         name: &str,
         handler: Box<dyn FnMut(Query) + Send + Sync + 'static>,
     ) -> Result<Queryable<()>, ZenohInterfaceError> {
+        self.validate()?;
         let prv_cfg = self
             .get_provider_config(name)
             .ok_or_else(|| ZenohInterfaceError::PrvEndpointNotFound(name.to_string()))?;
@@ -297,23 +767,31 @@ impl ZenohInterface {
     }
 }
 
-fn is_port_in_use(port: u16) -> bool {
-    let addr = SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, port);
-    TcpListener::bind(addr).map_err(
-        // print the error
-        |e| eprintln!("Failed to bind to port {}: {}", port, e),
-    ).is_err()
+fn resolve_encoder<T>(
+    registry: &EncoderRegistry<T>,
+    name: &str,
+    message_type: &str,
+    encoding: Option<&str>,
+) -> Result<BoxedEncoder<T>, ZenohInterfaceError> {
+    registry
+        .check_message_type(message_type)
+        .and_then(|_| registry.resolve(encoding))
+        .map_err(|source| ZenohInterfaceError::Encoding {
+            name: name.to_string(),
+            source,
+        })
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::interfaces::zenoh::model::ZenohPublisherConfig;
+    use crate::interfaces::zenoh::model::{HandlerChannel, ZenohPublisherConfig};
     use crate::models::{
         ApplicationEnvConfig, InterfaceConfig, ProviderEndpointConfig, PublisherTopicConfig,
         RequesterEndpointConfig, SubscriberTopicConfig,
     };
     use crate::models::{ApplicationInfo, MountedPeripherals};
+    use crate::testing::{AppFixture, TEST_MESSAGE_TYPE};
     use serde_json::json;
     use std::collections::BTreeMap;
     use zenoh::qos;
@@ -348,6 +826,7 @@ mod tests {
             providers: BTreeMap::new(),
             clients: BTreeMap::new(),
             servers: BTreeMap::new(),
+            config: BTreeMap::new(),
         }
     }
 
@@ -562,6 +1041,1057 @@ mod tests {
         assert!(queryable.is_ok());
     }
 
+    #[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+    struct TypedMessage {
+        id: u32,
+        body: String,
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn test_typed_publisher_subscriber_roundtrip() {
+        use crate::encodings::JsonEncoder;
+
+        let iface = AppFixture::new("typed")
+            .publisher("OUT", "typed/roundtrip")
+            .subscriber("IN", "typed/roundtrip")
+            .zenoh_interface();
+        let session = iface.get_session().await.unwrap();
+        let subscriber = iface
+            .get_typed_subscriber(&session, "IN", JsonEncoder::<TypedMessage>::new())
+            .await
+            .unwrap();
+        let publisher = iface
+            .get_typed_publisher(&session, "OUT", JsonEncoder::<TypedMessage>::new())
+            .await
+            .unwrap();
+
+        let message = TypedMessage {
+            id: 7,
+            body: "hello".into(),
+        };
+        publisher.publish(&message).await.unwrap();
+
+        let received = tokio::time::timeout(std::time::Duration::from_secs(5), subscriber.recv())
+            .await
+            .expect("timed out waiting for sample")
+            .unwrap();
+        assert_eq!(received, message);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn test_typed_subscriber_metadata() {
+        use crate::encodings::JsonEncoder;
+        use crate::interfaces::zenoh::typed::TypedSample;
+
+        let iface = AppFixture::new("app-1")
+            .publisher("OUT", "typed/metadata")
+            .subscriber("IN", "typed/metadata")
+            .zenoh_interface();
+        let session = iface.get_session().await.unwrap();
+        let subscriber = iface
+            .get_typed_subscriber(&session, "IN", JsonEncoder::new())
+            .await
+            .unwrap();
+        let mismatched = iface
+            .get_typed_subscriber(&session, "IN", JsonEncoder::<TypedMessage>::new())
+            .await
+            .unwrap()
+            .with_message_type("make87_messages.image.ImageJPEG");
+        let publisher = iface
+            .get_typed_publisher(&session, "OUT", JsonEncoder::new())
+            .await
+            .unwrap();
+
+        for id in 0..2 {
+            let message = TypedMessage {
+                id,
+                body: "hello".into(),
+            };
+            publisher.publish(&message).await.unwrap();
+        }
+
+        let timeout = std::time::Duration::from_secs(5);
+        for seq in 0..2 {
+            let sample: TypedSample<TypedMessage> =
+                tokio::time::timeout(timeout, subscriber.recv_sample())
+                    .await
+                    .expect("timed out waiting for sample")
+                    .unwrap();
+            let metadata = sample.metadata.unwrap();
+            assert_eq!(sample.value.id as u64, seq);
+            assert_eq!(metadata.seq, seq);
+            assert_eq!(metadata.message_type, TEST_MESSAGE_TYPE);
+            assert_eq!(metadata.encoding, "json");
+            assert_eq!(metadata.deployed_application_id, "app-1");
+            assert_eq!(sample.missed, 0);
+        }
+        assert_eq!(subscriber.missed_samples(), 0);
+
+        let result = tokio::time::timeout(timeout, mismatched.recv())
+            .await
+            .expect("timed out waiting for sample");
+        assert!(matches!(
+            result,
+            Err(ZenohInterfaceError::UnexpectedMessageType { ref received, .. })
+                if received == TEST_MESSAGE_TYPE
+        ));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn test_typed_publisher_buffers_until_matched() {
+        use crate::encodings::JsonEncoder;
+        use futures::StreamExt;
+
+        let iface = AppFixture::new("matching")
+            .publisher("OUT", "typed/matching")
+            .subscriber("IN", "typed/matching")
+            .entity_config("OUT", json!({"buffer_until_matched": 2}))
+            .zenoh_interface();
+        let session = iface.get_session().await.unwrap();
+        let publisher = iface
+            .get_typed_publisher(&session, "OUT", JsonEncoder::new())
+            .await
+            .unwrap();
+        let mut changes = publisher.matching_changed().await.unwrap();
+
+        let short = std::time::Duration::from_millis(100);
+        assert!(!publisher.is_matching().await.unwrap());
+        assert!(!publisher.wait_for_matching(short).await.unwrap());
+        for id in 0..3 {
+            let message = TypedMessage {
+                id,
+                body: "early".into(),
+            };
+            publisher.publish(&message).await.unwrap();
+        }
+        assert_eq!(publisher.pending().await, 2);
+
+        let subscriber = iface
+            .get_typed_subscriber(&session, "IN", JsonEncoder::new())
+            .await
+            .unwrap();
+        let timeout = std::time::Duration::from_secs(5);
+        assert!(publisher.wait_for_matching(timeout).await.unwrap());
+        let changed = tokio::time::timeout(timeout, changes.next()).await.unwrap();
+        assert_eq!(changed, Some(true));
+
+        // The oldest sample was dropped once the buffer was full.
+        for id in 1..3 {
+            let message: TypedMessage = tokio::time::timeout(timeout, subscriber.recv())
+                .await
+                .expect("timed out waiting for buffered sample")
+                .unwrap();
+            assert_eq!(message.id, id);
+        }
+        assert_eq!(publisher.pending().await, 0);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn test_late_joiner_receives_history() {
+        use crate::encodings::JsonEncoder;
+        use crate::interfaces::zenoh::PublicationCache;
+
+        let iface = AppFixture::new("history")
+            .publisher("OUT", "typed/history")
+            .subscriber("IN", "typed/history")
+            .entity_config("OUT", json!({"history_depth": 2}))
+            .entity_config("IN", json!({"request_history": true}))
+            .zenoh_interface();
+        let session = iface.get_session().await.unwrap();
+        let publisher = iface
+            .get_typed_publisher(&session, "OUT", JsonEncoder::new())
+            .await
+            .unwrap();
+        for id in 0..3 {
+            let message = TypedMessage {
+                id,
+                body: "calibration".into(),
+            };
+            publisher.publish(&message).await.unwrap();
+        }
+        assert_eq!(publisher.history().map(PublicationCache::len), Some(2));
+
+        let subscriber = iface
+            .get_typed_subscriber(&session, "IN", JsonEncoder::<TypedMessage>::new())
+            .await
+            .unwrap();
+        let timeout = std::time::Duration::from_secs(5);
+        for id in 1..3 {
+            let sample = tokio::time::timeout(timeout, subscriber.recv_sample())
+                .await
+                .expect("timed out waiting for history")
+                .unwrap();
+            assert_eq!(sample.value.id, id);
+            assert_eq!(sample.metadata.unwrap().seq, u64::from(id));
+        }
+
+        let live = TypedMessage {
+            id: 3,
+            body: "live".into(),
+        };
+        publisher.publish(&live).await.unwrap();
+        let message: TypedMessage = tokio::time::timeout(timeout, subscriber.recv())
+            .await
+            .expect("timed out waiting for live sample")
+            .unwrap();
+        assert_eq!(message, live);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn test_chunked_publish() {
+        use crate::encodings::JsonEncoder;
+
+        let iface = AppFixture::new("chunked")
+            .publisher("OUT", "typed/chunked")
+            .subscriber("IN", "typed/chunked")
+            .entity_config(
+                "OUT",
+                json!({"chunk_size": 64, "congestion_control": "BLOCK"}),
+            )
+            .zenoh_interface();
+        let session = iface.get_session().await.unwrap();
+        let subscriber = iface
+            .get_typed_subscriber(&session, "IN", JsonEncoder::<TypedMessage>::new())
+            .await
+            .unwrap();
+        let publisher = iface
+            .get_typed_publisher(&session, "OUT", JsonEncoder::new())
+            .await
+            .unwrap();
+        let timeout = std::time::Duration::from_secs(5);
+        assert!(publisher.wait_for_matching(timeout).await.unwrap());
+
+        let large = TypedMessage {
+            id: 7,
+            body: "point cloud ".repeat(100),
+        };
+        publisher.publish(&large).await.unwrap();
+        let sample = tokio::time::timeout(timeout, subscriber.recv_sample())
+            .await
+            .expect("timed out waiting for reassembled sample")
+            .unwrap();
+        assert_eq!(sample.value, large);
+        assert!(sample.metadata.is_some());
+
+        let stats = subscriber.subscriber().stats();
+        assert_eq!(stats.received, 1);
+        assert_eq!(stats.incomplete_transfers, 0);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn test_chunked_history() {
+        use crate::encodings::JsonEncoder;
+        use crate::interfaces::zenoh::history::history_key;
+        use zenoh::query::{ConsolidationMode, ReplyKeyExpr};
+
+        let iface = AppFixture::new("chunked")
+            .publisher("OUT", "typed/chunked_history")
+            .subscriber("IN", "typed/chunked_history")
+            .entity_config("OUT", json!({"chunk_size": 64, "history_depth": 1}))
+            .entity_config("IN", json!({"request_history": true}))
+            .zenoh_interface();
+        let session = iface.get_session().await.unwrap();
+        let publisher = iface
+            .get_typed_publisher(&session, "OUT", JsonEncoder::new())
+            .await
+            .unwrap();
+        let large = TypedMessage {
+            id: 7,
+            body: "point cloud ".repeat(100),
+        };
+        publisher.publish(&large).await.unwrap();
+
+        let replies = session
+            .get(history_key("typed/chunked_history"))
+            .consolidation(ConsolidationMode::None)
+            .accept_replies(ReplyKeyExpr::Any)
+            .await
+            .unwrap();
+        let mut chunks = 0;
+        while let Ok(reply) = replies.recv_async().await {
+            let sample = reply.into_result().unwrap();
+            // At most the chunk size plus the chunk header.
+            assert!(sample.payload().len() <= 64 + 24);
+            chunks += 1;
+        }
+        assert!(chunks > 1);
+
+        let subscriber = iface
+            .get_typed_subscriber(&session, "IN", JsonEncoder::<TypedMessage>::new())
+            .await
+            .unwrap();
+        let sample = tokio::time::timeout(std::time::Duration::from_secs(5), subscriber.recv())
+            .await
+            .expect("timed out waiting for reassembled history")
+            .unwrap();
+        assert_eq!(sample, large);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn test_owned_session_is_shared() {
+        use crate::encodings::JsonEncoder;
+
+        let iface = AppFixture::new("owned")
+            .publisher("OUT", "typed/owned_session")
+            .subscriber("IN", "typed/owned_session")
+            .zenoh_interface();
+        let other = iface.share_session("other");
+        let zid = iface.session().await.unwrap().zid();
+        assert_eq!(iface.session().await.unwrap().zid(), zid);
+        assert_eq!(other.session().await.unwrap().zid(), zid);
+
+        let subscriber = iface
+            .typed_subscriber("IN", JsonEncoder::<TypedMessage>::new())
+            .await
+            .unwrap();
+        let publisher = iface
+            .typed_publisher("OUT", JsonEncoder::new())
+            .await
+            .unwrap();
+        let timeout = std::time::Duration::from_secs(5);
+        assert!(publisher.wait_for_matching(timeout).await.unwrap());
+        let message = TypedMessage {
+            id: 1,
+            body: "owned".into(),
+        };
+        publisher.publish(&message).await.unwrap();
+        let received = tokio::time::timeout(timeout, subscriber.recv())
+            .await
+            .expect("timed out waiting for sample")
+            .unwrap();
+        assert_eq!(received, message);
+
+        other.close().await.unwrap();
+        assert!(iface.session().await.unwrap().is_closed());
+        assert!(iface.publisher("OUT").await.is_err());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn test_liveliness_peer_events() {
+        use crate::interfaces::zenoh::PeerKind;
+
+        let iface = AppFixture::new("app-alive")
+            .publisher("OUT", "typed/liveliness")
+            .subscriber("IN", "typed/liveliness")
+            .zenoh_interface();
+        let session = iface.get_session().await.unwrap();
+        let watcher = iface.watch_peers(&session).await.unwrap();
+        assert!(!watcher.is_alive(PeerKind::Publisher, "IN"));
+
+        let tokens = iface.declare_liveliness(&session).await.unwrap();
+        assert_eq!(tokens.len(), 2);
+        let timeout = std::time::Duration::from_secs(5);
+        let event = tokio::time::timeout(timeout, watcher.recv_async())
+            .await
+            .expect("timed out waiting for peer event")
+            .unwrap();
+        assert_eq!(event.kind, PeerKind::Publisher);
+        assert_eq!(event.name, "IN");
+        assert_eq!(event.deployed_application_id, "app-alive");
+        assert!(event.alive);
+        assert_eq!(
+            watcher.alive_peers(PeerKind::Publisher, "IN"),
+            vec!["app-alive".to_string()]
+        );
+
+        tokens.undeclare().await.unwrap();
+        let event = tokio::time::timeout(timeout, watcher.recv_async())
+            .await
+            .expect("timed out waiting for peer event")
+            .unwrap();
+        assert!(!event.alive);
+        assert!(!watcher.is_alive(PeerKind::Publisher, "IN"));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn test_owned_session_declares_liveliness() {
+        use crate::interfaces::zenoh::PeerKind;
+
+        let iface = AppFixture::new("app-owned")
+            .publisher("OUT", "owned/liveliness")
+            .subscriber("IN", "owned/liveliness")
+            .zenoh_interface();
+        let session = iface.session().await.unwrap();
+        let watcher = iface.watch_peers(session).await.unwrap();
+        let timeout = std::time::Duration::from_secs(5);
+        let event = tokio::time::timeout(timeout, watcher.recv_async())
+            .await
+            .expect("timed out waiting for peer event")
+            .unwrap();
+        assert_eq!(event.name, "IN");
+        assert_eq!(event.deployed_application_id, "app-owned");
+        assert!(watcher.is_alive(PeerKind::Publisher, "IN"));
+        iface.close().await.unwrap();
+
+        let iface = AppFixture::new("app/*").zenoh_interface();
+        assert!(matches!(
+            iface.session().await,
+            Err(ZenohInterfaceError::InvalidApplicationId(id)) if id == "app/*"
+        ));
+        // Without an id there is nothing to declare, but the session still opens.
+        let iface = AppFixture::new("").zenoh_interface();
+        let session = iface.session().await.unwrap();
+        assert!(matches!(
+            iface.declare_liveliness(session).await,
+            Err(ZenohInterfaceError::InvalidApplicationId(_))
+        ));
+        iface.close().await.unwrap();
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn test_subscriber_keep_every_nth() {
+        use crate::encodings::JsonEncoder;
+
+        let iface = AppFixture::new("downsampled")
+            .publisher("OUT", "typed/downsampled")
+            .subscriber("IN", "typed/downsampled")
+            .entity_config("OUT", json!({"reliability": "RELIABLE"}))
+            .entity_config("IN", json!({"keep_every_nth": 2}))
+            .zenoh_interface();
+        let session = iface.get_session().await.unwrap();
+        let subscriber = iface
+            .get_typed_subscriber(&session, "IN", JsonEncoder::new())
+            .await
+            .unwrap();
+        let publisher = iface
+            .get_typed_publisher(&session, "OUT", JsonEncoder::new())
+            .await
+            .unwrap();
+
+        for id in 0..5 {
+            let message = TypedMessage {
+                id,
+                body: "frame".into(),
+            };
+            publisher.publish(&message).await.unwrap();
+        }
+
+        let timeout = std::time::Duration::from_secs(5);
+        for id in [0, 2, 4] {
+            let message: TypedMessage = tokio::time::timeout(timeout, subscriber.recv())
+                .await
+                .expect("timed out waiting for sample")
+                .unwrap();
+            assert_eq!(message.id, id);
+        }
+        assert_eq!(subscriber.subscriber().stats().received, 3);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn test_synchronizer_exact() {
+        use crate::encodings::JsonEncoder;
+        use crate::interfaces::zenoh::SyncPolicy;
+
+        let iface = AppFixture::new("synced")
+            .publisher("OUT", "typed/synced")
+            .subscriber("IN", "typed/synced")
+            .subscriber("IN_COPY", "typed/synced")
+            .zenoh_interface();
+        let session = iface.get_session().await.unwrap();
+        let mut synchronizer = iface
+            .get_synchronizer(&session, &["IN", "IN_COPY"], SyncPolicy::Exact)
+            .await
+            .unwrap()
+            .with_queue_size(4);
+        let publisher = iface
+            .get_typed_publisher(&session, "OUT", JsonEncoder::new())
+            .await
+            .unwrap();
+
+        let message = TypedMessage {
+            id: 7,
+            body: "synced".into(),
+        };
+        publisher.publish(&message).await.unwrap();
+
+        let timeout = std::time::Duration::from_secs(5);
+        let synced = tokio::time::timeout(timeout, synchronizer.recv())
+            .await
+            .expect("timed out waiting for tuple")
+            .unwrap();
+        assert_eq!(synced.samples.len(), 2);
+        assert_eq!(synced.stamps_ns[0], synced.stamps_ns[1]);
+        assert_eq!(synchronizer.unstamped(), 0);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn test_synchronizer_unknown_subscriber() {
+        use crate::interfaces::zenoh::SyncPolicy;
+
+        let iface = AppFixture::new("synced")
+            .publisher("OUT", "typed/synced")
+            .subscriber("IN", "typed/synced")
+            .zenoh_interface();
+        let session = iface.get_session().await.unwrap();
+        let result = iface
+            .get_synchronizer(&session, &["IN", "MISSING"], SyncPolicy::Exact)
+            .await;
+        assert!(matches!(
+            result,
+            Err(ZenohInterfaceError::SubTopicNotFound(ref name)) if name == "MISSING"
+        ));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn test_bag_record_and_replay() {
+        use crate::encodings::JsonEncoder;
+        use crate::interfaces::zenoh::ReplayMode;
+
+        // Replay goes through the publisher named like the recorded subscriber.
+        let iface = AppFixture::new("bag")
+            .publisher("CAMERA", "typed/bag")
+            .subscriber("CAMERA", "typed/bag")
+            .entity_config("CAMERA", json!({"reliability": "RELIABLE"}))
+            .zenoh_interface();
+        let session = iface.get_session().await.unwrap();
+        let tmpdir = tempfile::TempDir::new().unwrap();
+        let path = tmpdir.path().join("replay.bag");
+        let timeout = std::time::Duration::from_secs(5);
+
+        let mut recorder = iface
+            .get_bag_recorder(&session, &["CAMERA"], &path)
+            .await
+            .unwrap();
+        let publisher = iface
+            .get_typed_publisher(&session, "CAMERA", JsonEncoder::new())
+            .await
+            .unwrap();
+        for id in 0..3 {
+            let message = TypedMessage {
+                id,
+                body: "recorded".into(),
+            };
+            publisher.publish(&message).await.unwrap();
+            tokio::time::timeout(timeout, recorder.record_next())
+                .await
+                .expect("timed out recording")
+                .unwrap();
+        }
+        assert_eq!(recorder.finish().unwrap(), 3);
+
+        let subscriber = iface
+            .get_typed_subscriber(&session, "CAMERA", JsonEncoder::<TypedMessage>::new())
+            .await
+            .unwrap();
+        let mut replayer = iface
+            .get_bag_replayer(&session, &path)
+            .await
+            .unwrap()
+            .with_mode(ReplayMode::Stepped);
+        assert_eq!(replayer.reader().topics(), ["CAMERA"]);
+        let record = replayer.step().await.unwrap().unwrap();
+        assert_eq!(record.encoding, "json");
+        assert_eq!(record.key, "typed/bag");
+        let mut replayer = replayer.with_mode(ReplayMode::Timed { rate: 100.0 });
+        let replayed = replayer.replay().await.unwrap();
+        assert_eq!(replayed, 2);
+
+        for id in 0..3 {
+            let sample = tokio::time::timeout(timeout, subscriber.recv_sample())
+                .await
+                .expect("timed out waiting for replayed sample")
+                .unwrap();
+            assert_eq!(sample.value.id, id);
+            assert_eq!(sample.missed, 0);
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn test_typed_subscriber_decode_error() {
+        use crate::encodings::JsonEncoder;
+
+        let iface = AppFixture::new("decode")
+            .publisher("OUT", "typed/decode")
+            .subscriber("IN", "typed/decode")
+            .zenoh_interface();
+        let session = iface.get_session().await.unwrap();
+        let subscriber = iface
+            .get_typed_subscriber(&session, "IN", JsonEncoder::<TypedMessage>::new())
+            .await
+            .unwrap();
+        let publisher = iface.get_publisher(&session, "OUT").await.unwrap();
+
+        publisher.put("not json").await.unwrap();
+
+        let result = tokio::time::timeout(std::time::Duration::from_secs(5), subscriber.recv())
+            .await
+            .expect("timed out waiting for sample");
+        assert!(matches!(result, Err(ZenohInterfaceError::Decode(_))));
+        assert_eq!(subscriber.subscriber().stats().decode_failures, 1);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn test_typed_from_registry_uses_configured_encoding() {
+        let iface = AppFixture::new("registry")
+            .publisher("OUT", "typed/registry")
+            .subscriber("IN", "typed/registry")
+            .zenoh_interface();
+        let session = iface.get_session().await.unwrap();
+        let registry = EncoderRegistry::<TypedMessage>::serde();
+        let subscriber = iface
+            .get_typed_subscriber_from_registry(&session, "IN", &registry)
+            .await
+            .unwrap();
+        let publisher = iface
+            .get_typed_publisher_from_registry(&session, "OUT", &registry)
+            .await
+            .unwrap();
+
+        let message = TypedMessage {
+            id: 3,
+            body: "from registry".into(),
+        };
+        publisher.publish(&message).await.unwrap();
+
+        let received = tokio::time::timeout(std::time::Duration::from_secs(5), subscriber.recv())
+            .await
+            .expect("timed out waiting for sample")
+            .unwrap();
+        assert_eq!(received, message);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn test_typed_from_registry_encoding_mismatch() {
+        let mut config = AppFixture::new("registry")
+            .publisher("OUT", "typed/registry")
+            .build();
+        config
+            .interfaces
+            .get_mut("zenoh")
+            .unwrap()
+            .publishers
+            .get_mut("OUT")
+            .unwrap()
+            .encoding = Some("cbor".into());
+        let iface = ZenohInterface::new(config, "zenoh");
+        let session = iface.get_session().await.unwrap();
+        let registry = EncoderRegistry::<TypedMessage>::serde();
+
+        let result = iface
+            .get_typed_publisher_from_registry(&session, "OUT", &registry)
+            .await;
+        match result {
+            Err(ZenohInterfaceError::Encoding { name, source }) => {
+                assert_eq!(name, "OUT");
+                assert!(matches!(source, EncodingError::NotRegistered(_)));
+            }
+            _ => panic!("Expected Encoding error"),
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn test_configured_subscriber_stream() {
+        use futures::StreamExt;
+
+        let iface = AppFixture::new("stream")
+            .publisher("OUT", "typed/stream")
+            .subscriber("IN", "typed/stream")
+            .zenoh_interface();
+        let session = iface.get_session().await.unwrap();
+        let subscriber = iface.get_subscriber(&session, "IN").await.unwrap();
+        let publisher = iface.get_publisher(&session, "OUT").await.unwrap();
+
+        for body in ["skip", "keep"] {
+            publisher.put(body).await.unwrap();
+        }
+
+        let bodies = subscriber.filter_map(|sample| async move {
+            let body = sample.payload().try_to_string().ok()?.into_owned();
+            (body != "skip").then_some(body)
+        });
+        futures::pin_mut!(bodies);
+        let first = tokio::time::timeout(std::time::Duration::from_secs(5), bodies.next())
+            .await
+            .expect("timed out waiting for sample");
+        assert_eq!(first.as_deref(), Some("keep"));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_typed_rpc_roundtrip_and_error() {
+        use crate::interfaces::zenoh::rpc::RpcErrorKind;
+
+        let iface = AppFixture::new("rpc")
+            .requester("QUERY", "typed/rpc")
+            .provider("REPLY", "typed/rpc")
+            .zenoh_interface();
+        let session = iface.get_session().await.unwrap();
+        let requests = EncoderRegistry::<TypedMessage>::serde().for_message_type(TEST_MESSAGE_TYPE);
+        let responses = EncoderRegistry::<String>::serde().for_message_type(TEST_MESSAGE_TYPE);
+
+        let provider = iface
+            .get_typed_queryable(&session, "REPLY", &requests, &responses)
+            .await
+            .unwrap();
+        tokio::spawn(async move {
+            provider
+                .serve(|req: TypedMessage| async move {
+                    if req.id == 0 {
+                        return Err(RpcError::handler("id must not be zero"));
+                    }
+                    Ok(req.body.chars().rev().collect::<String>())
+                })
+                .await
+        });
+
+        let querier = iface
+            .get_typed_querier(&session, "QUERY", &requests, &responses)
+            .await
+            .unwrap()
+            .with_timeout(std::time::Duration::from_secs(5));
+
+        let reply = querier
+            .call(&TypedMessage {
+                id: 1,
+                body: "hello".into(),
+            })
+            .await
+            .unwrap();
+        assert_eq!(reply, "olleh");
+
+        let result = querier
+            .call(&TypedMessage {
+                id: 0,
+                body: "hello".into(),
+            })
+            .await;
+        match result {
+            Err(ZenohInterfaceError::Rpc(err)) => {
+                assert_eq!(err.kind, RpcErrorKind::Handler);
+                assert_eq!(err.message, "id must not be zero");
+            }
+            other => panic!("Expected Rpc error, got {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_typed_querier_timeout_and_call_options() {
+        use crate::interfaces::zenoh::rpc::CallOptions;
+
+        let iface = AppFixture::new("rpc")
+            .requester("QUERY", "typed/slow_rpc")
+            .provider("REPLY", "typed/slow_rpc")
+            .entity_config("QUERY", json!({"timeout_ms": 200}))
+            .zenoh_interface();
+        let session = iface.get_session().await.unwrap();
+        let requests = EncoderRegistry::<TypedMessage>::serde();
+        let responses = EncoderRegistry::<String>::serde();
+
+        let provider = iface
+            .get_typed_queryable(&session, "REPLY", &requests, &responses)
+            .await
+            .unwrap();
+        tokio::spawn(async move {
+            provider
+                .serve(|req: TypedMessage| async move {
+                    tokio::time::sleep(std::time::Duration::from_millis(600)).await;
+                    Ok(req.body)
+                })
+                .await
+        });
+
+        let querier = iface
+            .get_typed_querier(&session, "QUERY", &requests, &responses)
+            .await
+            .unwrap();
+        assert_eq!(querier.timeout(), std::time::Duration::from_millis(200));
+        let message = TypedMessage {
+            id: 1,
+            body: "slow".into(),
+        };
+
+        let result = querier.call(&message).await;
+        assert!(matches!(result, Err(ZenohInterfaceError::Timeout(_))));
+
+        let options = CallOptions::default().timeout(std::time::Duration::from_secs(5));
+        assert_eq!(querier.call_with(&message, &options).await.unwrap(), "slow");
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_typed_queryable_serves_concurrently() {
+        let iface = AppFixture::new("rpc")
+            .requester("QUERY", "typed/concurrent_rpc")
+            .provider("REPLY", "typed/concurrent_rpc")
+            .zenoh_interface();
+        let session = iface.get_session().await.unwrap();
+        let requests = EncoderRegistry::<TypedMessage>::serde();
+        let responses = EncoderRegistry::<String>::serde();
+
+        let provider = iface
+            .get_typed_queryable(&session, "REPLY", &requests, &responses)
+            .await
+            .unwrap();
+        tokio::spawn(async move {
+            provider
+                .serve_concurrently(2, |req: TypedMessage| async move {
+                    if req.body == "slow" {
+                        tokio::time::sleep(std::time::Duration::from_secs(3)).await;
+                    }
+                    Ok(req.body)
+                })
+                .await
+        });
+
+        let querier = iface
+            .get_typed_querier(&session, "QUERY", &requests, &responses)
+            .await
+            .unwrap()
+            .with_timeout(std::time::Duration::from_secs(5));
+        let slow = TypedMessage {
+            id: 1,
+            body: "slow".into(),
+        };
+        let fast = TypedMessage {
+            id: 2,
+            body: "fast".into(),
+        };
+        let (slow, fast) = tokio::join!(querier.call(&slow), async {
+            tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+            let started = std::time::Instant::now();
+            let reply = querier.call(&fast).await;
+            (reply, started.elapsed())
+        });
+        assert_eq!(slow.unwrap(), "slow");
+        let (fast, elapsed) = fast;
+        assert_eq!(fast.unwrap(), "fast");
+        assert!(elapsed < std::time::Duration::from_secs(2), "{elapsed:?}");
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn test_typed_querier_message_type_mismatch() {
+        let iface = AppFixture::new("rpc")
+            .requester("QUERY", "typed/rpc")
+            .provider("REPLY", "typed/rpc")
+            .zenoh_interface();
+        let session = iface.get_session().await.unwrap();
+        let requests = EncoderRegistry::<TypedMessage>::serde().for_message_type("OtherType");
+        let responses = EncoderRegistry::<String>::serde();
+
+        let result = iface
+            .get_typed_querier(&session, "QUERY", &requests, &responses)
+            .await;
+        assert!(matches!(
+            result,
+            Err(ZenohInterfaceError::Encoding {
+                source: EncodingError::MessageTypeMismatch { .. },
+                ..
+            })
+        ));
+    }
+
+    fn listen_config(settings: serde_json::Value) -> ApplicationEnvConfig {
+        let mut config = default_app_config();
+        let mut iface_config = make_interface_config();
+        iface_config.config = serde_json::from_value(settings).unwrap();
+        config.interfaces.insert("zenoh".into(), iface_config);
+        config
+    }
+
+    #[test]
+    fn test_zenoh_config_listen_endpoints() {
+        let iface = ZenohInterface::new(default_app_config(), "zenoh");
+        let cfg = iface.zenoh_config().unwrap();
+        assert_eq!(
+            cfg.get_json("listen/endpoints").unwrap(),
+            json!(["tcp/0.0.0.0:7447"]).to_string()
+        );
+
+        let iface = ZenohInterface::new(
+            listen_config(json!({"listen_protocol": "UDP", "listen_port": 7500})),
+            "zenoh",
+        );
+        let cfg = iface.zenoh_config().unwrap();
+        assert_eq!(
+            cfg.get_json("listen/endpoints").unwrap(),
+            json!(["udp/0.0.0.0:7500"]).to_string()
+        );
+    }
+
+    #[test]
+    fn test_connect_endpoints_cover_all_bound_entities() {
+        use crate::models::{
+            AccessPoint, BoundClient, BoundRequester, BoundSubscriber, ClientServiceConfig,
+        };
+
+        let access_point = |vpn_ip: &str, public_ip: Option<&str>| AccessPoint {
+            vpn_ip: vpn_ip.into(),
+            vpn_port: 7447,
+            public_ip: public_ip.map(Into::into),
+            public_port: public_ip.map(|_| 17447),
+            same_node: false,
+        };
+        let mut config = listen_config(json!({"connect_policy": "PUBLIC"}));
+        let iface_config = config.interfaces.get_mut("zenoh").unwrap();
+        iface_config.subscribers.insert(
+            "SUB".into(),
+            BoundSubscriber {
+                access_point: access_point("10.0.0.2", None),
+                config: sub_topic_config(),
+            },
+        );
+        iface_config.requesters.insert(
+            "REQ".into(),
+            BoundRequester {
+                access_point: access_point("10.0.0.3", Some("203.0.113.3")),
+                config: req_endpoint_config(),
+            },
+        );
+        iface_config.clients.insert(
+            "CLIENT".into(),
+            BoundClient {
+                access_point: access_point("10.0.0.2", None),
+                config: ClientServiceConfig {
+                    name: "CLIENT".into(),
+                    spec: "spec".into(),
+                    key: "client_key".into(),
+                    interface_name: "zenoh".into(),
+                    config: BTreeMap::new(),
+                    protocol: "zenoh".into(),
+                },
+            },
+        );
+
+        let iface = ZenohInterface::new(config, "zenoh");
+        assert_eq!(
+            iface.connect_endpoints().unwrap(),
+            vec!["tcp/10.0.0.2:7447", "tcp/203.0.113.3:17447"]
+        );
+        let cfg = iface.zenoh_config().unwrap();
+        assert_eq!(
+            cfg.get_json("connect/endpoints").unwrap(),
+            json!(["tcp/10.0.0.2:7447", "tcp/203.0.113.3:17447"]).to_string()
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn test_get_session_listen_policy() {
+        let occupied = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint = format!("tcp/{}", occupied.local_addr().unwrap());
+
+        let iface = ZenohInterface::new(
+            listen_config(json!({"listen_endpoints": [endpoint], "listen_policy": "STRICT"})),
+            "zenoh",
+        );
+        match iface.get_session().await {
+            Err(ZenohInterfaceError::Listen { endpoints, .. }) => {
+                assert_eq!(endpoints, vec![endpoint.clone()])
+            }
+            other => panic!("Expected Listen error, got {:?}", other.map(|_| ())),
+        }
+
+        let iface = ZenohInterface::new(
+            listen_config(json!({"listen_endpoints": [endpoint], "listen_policy": "FALLBACK"})),
+            "zenoh",
+        );
+        let session = iface.get_session().await.unwrap();
+        let fallback = iface.listen_fallback().unwrap();
+        assert_eq!(fallback.endpoints, vec![endpoint.clone()]);
+        assert_eq!(fallback.fallback, vec!["tcp/127.0.0.1:0".to_string()]);
+        assert!(fallback.reason.contains("listener"), "{}", fallback.reason);
+        session.close().await.unwrap();
+
+        // Failures other than binding are neither retried nor reported as listen errors.
+        let iface = ZenohInterface::new(
+            listen_config(json!({
+                "listen_endpoints": ["tcp/127.0.0.1:0"],
+                "connect_endpoints": ["foo/127.0.0.1:1"],
+                "listen_policy": "FALLBACK",
+            })),
+            "zenoh",
+        );
+        match iface.get_session().await {
+            Err(ZenohInterfaceError::Zenoh(_)) => {}
+            other => panic!("Expected a zenoh error, got {:?}", other.map(|_| ())),
+        }
+        assert_eq!(iface.listen_fallback(), None);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn test_raw_publisher_metadata_stamper() {
+        use crate::encodings::{Encoder, JsonEncoder};
+
+        let iface = AppFixture::new("raw")
+            .publisher("OUT", "raw/metadata")
+            .subscriber("IN", "raw/metadata")
+            .zenoh_interface();
+        let session = iface.session().await.unwrap();
+        let subscriber = iface
+            .get_typed_subscriber(session, "IN", JsonEncoder::<TypedMessage>::new())
+            .await
+            .unwrap();
+        let publisher = iface.get_publisher(session, "OUT").await.unwrap();
+        let stamper = iface.get_metadata_stamper("OUT", &publisher).unwrap();
+        assert!(matches!(
+            iface.get_metadata_stamper("NOPE", &publisher),
+            Err(ZenohInterfaceError::PubTopicNotFound(_))
+        ));
+
+        let message = TypedMessage {
+            id: 1,
+            body: "raw".into(),
+        };
+        let payload = JsonEncoder::new().encode(&message).unwrap();
+        publisher
+            .put(payload.clone())
+            .attachment(stamper.next().to_attachment().unwrap())
+            .await
+            .unwrap();
+        // Without an attachment, the sample arrives without metadata.
+        publisher.put(payload).await.unwrap();
+
+        let timeout = std::time::Duration::from_secs(5);
+        let stamped = tokio::time::timeout(timeout, subscriber.recv_sample())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(stamped.value, message);
+        let metadata = stamped.metadata.unwrap();
+        assert_eq!(metadata.seq, 0);
+        assert_eq!(metadata.message_type, TEST_MESSAGE_TYPE);
+        let bare = tokio::time::timeout(timeout, subscriber.recv_sample())
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(bare.metadata.is_none());
+        iface.close().await.unwrap();
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn test_invalid_config_is_rejected_before_opening() {
+        let fixture = AppFixture::new("validation")
+            .publisher("OUT", "validation/out")
+            .subscriber("IN", "validation/in");
+        let valid = fixture.zenoh_interface();
+        let session = valid.session().await.unwrap();
+
+        let iface = fixture
+            .clone()
+            .entity_config("OUT", json!({"priority": "URGENT", "colour": "red"}))
+            .zenoh_interface();
+        let Err(ZenohInterfaceError::InvalidConfig(report)) = iface.session().await else {
+            panic!("Expected InvalidConfig from session()");
+        };
+        assert_eq!(report, iface.validation_report());
+        assert_eq!(report.issues[0].unknown_keys, vec!["colour"]);
+        assert!(matches!(
+            iface.get_session().await,
+            Err(ZenohInterfaceError::InvalidConfig(_))
+        ));
+        // Every entity is refused on an already open session too.
+        assert!(matches!(
+            iface.get_subscriber(session, "IN").await,
+            Err(ZenohInterfaceError::InvalidConfig(_))
+        ));
+
+        let iface = fixture
+            .interface_config(json!({"listen_policy": "SOMETIMES"}))
+            .zenoh_interface();
+        assert!(matches!(
+            iface.get_publisher(session, "OUT").await,
+            Err(ZenohInterfaceError::InvalidConfig(_))
+        ));
+        valid.close().await.unwrap();
+    }
+
     #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
     async fn test_get_publisher_not_found() {
         let config = default_app_config();
@@ -0,0 +1,332 @@
+use crate::interfaces::zenoh::{
+    HandlerChannel, HandlerReceiver, ZenohInterface, ZenohInterfaceError, DEFAULT_HANDLER_CAPACITY,
+};
+use futures::Stream;
+use std::collections::{BTreeMap, BTreeSet};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use zenoh::liveliness::LivelinessToken;
+use zenoh::pubsub::Subscriber;
+use zenoh::sample::{Sample, SampleKind};
+use zenoh::Result as ZResult;
+use zenoh::Session;
+
+/// Root of the key expressions liveliness tokens are declared on.
+///
+/// An application declares `make87/alive/<deployed_application_id>`, plus
+/// `make87/alive/<deployed_application_id>/<kind>/<key>` for each topic it publishes and
+/// each endpoint or service it provides.
+pub const LIVELINESS_PREFIX: &str = "make87/alive";
+
+/// `deployed_application_id` if it is a single, non-empty key expression chunk without
+/// wildcards, so that it matches only its own tokens.
+fn liveliness_id(deployed_application_id: &str) -> Result<&str, ZenohInterfaceError> {
+    let invalid = deployed_application_id.is_empty()
+        || deployed_application_id.contains(['/', '*', '$', '?', '#']);
+    if invalid {
+        return Err(ZenohInterfaceError::InvalidApplicationId(
+            deployed_application_id.to_string(),
+        ));
+    }
+    Ok(deployed_application_id)
+}
+
+/// The side of a connection a liveliness token stands for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum PeerKind {
+    /// The publisher behind a configured subscriber.
+    Publisher,
+    /// The provider behind a configured requester.
+    Provider,
+    /// The server behind a configured client.
+    Server,
+}
+
+impl PeerKind {
+    fn segment(&self) -> &'static str {
+        match self {
+            PeerKind::Publisher => "pub",
+            PeerKind::Provider => "prv",
+            PeerKind::Server => "srv",
+        }
+    }
+
+    fn token_key(&self, deployed_application_id: &str, key: &str) -> String {
+        format!(
+            "{LIVELINESS_PREFIX}/{deployed_application_id}/{}/{key}",
+            self.segment()
+        )
+    }
+}
+
+/// A peer coming up or going down.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PeerEvent {
+    pub kind: PeerKind,
+    /// Name of the subscriber, requester or client the peer serves.
+    pub name: String,
+    pub deployed_application_id: String,
+    pub alive: bool,
+}
+
+/// Liveliness tokens of a session, undeclared when dropped.
+pub struct LivelinessTokens {
+    tokens: Vec<LivelinessToken>,
+}
+
+impl LivelinessTokens {
+    pub fn len(&self) -> usize {
+        self.tokens.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tokens.is_empty()
+    }
+
+    pub async fn undeclare(self) -> ZResult<()> {
+        for token in self.tokens {
+            token.undeclare().await?;
+        }
+        Ok(())
+    }
+}
+
+type AlivePeers = Arc<Mutex<BTreeMap<(PeerKind, String), BTreeSet<String>>>>;
+
+/// Liveliness of the peers referenced in an interface config, as a stream of
+/// [`PeerEvent`]s and a snapshot of which peers are currently alive.
+///
+/// Peers that were already alive when the watcher was declared are reported as well.
+/// Events are kept in a ring of [`DEFAULT_HANDLER_CAPACITY`], so a watcher only used for
+/// [`is_alive`](Self::is_alive) never holds up zenoh; unread events are overwritten,
+/// oldest first.
+pub struct PeerWatcher {
+    subscribers: Vec<Subscriber<()>>,
+    receiver: HandlerReceiver<PeerEvent>,
+    alive: AlivePeers,
+}
+
+impl PeerWatcher {
+    pub async fn recv_async(&self) -> ZResult<PeerEvent> {
+        self.receiver.recv_async().await
+    }
+
+    pub fn try_recv(&self) -> ZResult<Option<PeerEvent>> {
+        self.receiver.try_recv()
+    }
+
+    /// Whether at least one peer of `kind` serving `name` is alive.
+    pub fn is_alive(&self, kind: PeerKind, name: &str) -> bool {
+        !self.alive_peers(kind, name).is_empty()
+    }
+
+    /// Deployed application ids of the live peers of `kind` serving `name`.
+    pub fn alive_peers(&self, kind: PeerKind, name: &str) -> Vec<String> {
+        let alive = self.alive.lock().unwrap_or_else(|e| e.into_inner());
+        alive
+            .get(&(kind, name.to_string()))
+            .map(|ids| ids.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    pub async fn undeclare(self) -> ZResult<()> {
+        for subscriber in self.subscribers {
+            subscriber.undeclare().await?;
+        }
+        Ok(())
+    }
+}
+
+impl Stream for PeerWatcher {
+    type Item = PeerEvent;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<PeerEvent>> {
+        Pin::new(&mut self.receiver).poll_next(cx)
+    }
+}
+
+/// Turn a liveliness sample on `make87/alive/<id>/<kind>/<key>` into a [`PeerEvent`].
+fn peer_event(kind: PeerKind, name: &str, sample: &Sample) -> Option<PeerEvent> {
+    let key = sample.key_expr().as_str();
+    let rest = key.strip_prefix(LIVELINESS_PREFIX)?.strip_prefix('/')?;
+    let (deployed_application_id, _) = rest.split_once('/')?;
+    Some(PeerEvent {
+        kind,
+        name: name.to_string(),
+        deployed_application_id: deployed_application_id.to_string(),
+        alive: sample.kind() == SampleKind::Put,
+    })
+}
+
+impl ZenohInterface {
+    /// Declare this application's liveliness tokens on `session`: one keyed by its
+    /// `deployed_application_id`, and one for each publisher, provider and server.
+    ///
+    /// Peers see the application as alive for as long as the returned tokens are kept.
+    /// [`ZenohInterface::session`] declares them on the owned session by itself.
+    ///
+    /// Fails with [`ZenohInterfaceError::InvalidApplicationId`] if the id is empty or holds
+    /// `/`, wildcards or other characters that would change what the keys match.
+    pub async fn declare_liveliness(
+        &self,
+        session: &Session,
+    ) -> Result<LivelinessTokens, ZenohInterfaceError> {
+        let app_id = liveliness_id(&self.application_info().deployed_application_id)?;
+        let mut keys = vec![format!("{LIVELINESS_PREFIX}/{app_id}")];
+        if let Some(iface) = self.interface_config() {
+            keys.extend(
+                iface
+                    .publishers
+                    .values()
+                    .map(|p| PeerKind::Publisher.token_key(app_id, &p.topic_key)),
+            );
+            keys.extend(
+                iface
+                    .providers
+                    .values()
+                    .map(|p| PeerKind::Provider.token_key(app_id, &p.endpoint_key)),
+            );
+            keys.extend(
+                iface
+                    .servers
+                    .values()
+                    .map(|s| PeerKind::Server.token_key(app_id, &s.key)),
+            );
+        }
+
+        let mut tokens = Vec::with_capacity(keys.len());
+        for key in keys {
+            tokens.push(session.liveliness().declare_token(key).await?);
+        }
+        Ok(LivelinessTokens { tokens })
+    }
+
+    /// Watch the liveliness of the publishers behind the configured subscribers, the
+    /// providers behind the requesters and the servers behind the clients.
+    pub async fn watch_peers(&self, session: &Session) -> Result<PeerWatcher, ZenohInterfaceError> {
+        let mut peers = Vec::new();
+        if let Some(iface) = self.interface_config() {
+            for (name, sub) in &iface.subscribers {
+                peers.push((PeerKind::Publisher, name, &sub.config.topic_key));
+            }
+            for (name, req) in &iface.requesters {
+                peers.push((PeerKind::Provider, name, &req.config.endpoint_key));
+            }
+            for (name, client) in &iface.clients {
+                peers.push((PeerKind::Server, name, &client.config.key));
+            }
+        }
+
+        let (callback, receiver) = HandlerReceiver::new(&HandlerChannel::Ring {
+            capacity: DEFAULT_HANDLER_CAPACITY,
+        });
+        let alive = AlivePeers::default();
+        let mut subscribers = Vec::with_capacity(peers.len());
+        for (kind, name, key) in peers {
+            let callback = callback.clone();
+            let alive = alive.clone();
+            let name = name.clone();
+            let subscriber = session
+                .liveliness()
+                .declare_subscriber(kind.token_key("*", key))
+                .history(true)
+                .callback(move |sample| {
+                    let Some(event) = peer_event(kind, &name, &sample) else {
+                        return;
+                    };
+                    {
+                        let mut alive = alive.lock().unwrap_or_else(|e| e.into_inner());
+                        let ids = alive.entry((kind, name.clone())).or_default();
+                        if event.alive {
+                            ids.insert(event.deployed_application_id.clone());
+                        } else {
+                            ids.remove(&event.deployed_application_id);
+                        }
+                    }
+                    callback.call(event);
+                })
+                .await?;
+            subscribers.push(subscriber);
+        }
+        Ok(PeerWatcher {
+            subscribers,
+            receiver,
+            alive,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_key() {
+        assert_eq!(
+            PeerKind::Provider.token_key("app-1", "my/endpoint"),
+            "make87/alive/app-1/prv/my/endpoint"
+        );
+        assert_eq!(
+            PeerKind::Publisher.token_key("*", "topic"),
+            "make87/alive/*/pub/topic"
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn test_watch_peer_up_and_down() {
+        use crate::testing::{wire, AppFixture};
+        use std::time::Duration;
+
+        let mut camera = AppFixture::new("camera").publisher("IMAGE", "camera/image");
+        let mut detector = AppFixture::new("detector").subscriber("IMAGE", "camera/image");
+        wire(&mut camera, &mut detector);
+        let camera = camera.zenoh_interface();
+        let detector = detector.zenoh_interface();
+        let watcher = detector
+            .watch_peers(detector.session().await.unwrap())
+            .await
+            .unwrap();
+        assert!(!watcher.is_alive(PeerKind::Publisher, "IMAGE"));
+
+        let timeout = Duration::from_secs(10);
+        camera.session().await.unwrap();
+        let up = tokio::time::timeout(timeout, watcher.recv_async())
+            .await
+            .expect("timed out waiting for the peer to come up")
+            .unwrap();
+        assert_eq!(
+            up,
+            PeerEvent {
+                kind: PeerKind::Publisher,
+                name: "IMAGE".into(),
+                deployed_application_id: "camera".into(),
+                alive: true,
+            }
+        );
+        assert_eq!(
+            watcher.alive_peers(PeerKind::Publisher, "IMAGE"),
+            vec!["camera".to_string()]
+        );
+
+        camera.close().await.unwrap();
+        let down = tokio::time::timeout(timeout, watcher.recv_async())
+            .await
+            .expect("timed out waiting for the peer to go down")
+            .unwrap();
+        assert_eq!(down, PeerEvent { alive: false, ..up });
+        assert!(!watcher.is_alive(PeerKind::Publisher, "IMAGE"));
+        detector.close().await.unwrap();
+    }
+
+    #[test]
+    fn test_liveliness_id() {
+        assert_eq!(liveliness_id("app-1").unwrap(), "app-1");
+        for id in ["", "a/b", "*", "app-**", "$*", "a?b", "a#b"] {
+            assert!(matches!(
+                liveliness_id(id),
+                Err(ZenohInterfaceError::InvalidApplicationId(invalid)) if invalid == id
+            ));
+        }
+    }
+}
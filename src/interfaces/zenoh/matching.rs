@@ -0,0 +1,201 @@
+use crate::interfaces::zenoh::{
+    chunk, Chunker, HandlerChannel, HandlerReceiver, ZenohInterfaceError,
+};
+use futures::lock::Mutex;
+use futures::{Stream, StreamExt};
+use std::collections::VecDeque;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use zenoh::bytes::ZBytes;
+use zenoh::matching::MatchingListener;
+use zenoh::pubsub::Publisher;
+use zenoh::query::Querier;
+use zenoh::Result as ZResult;
+
+/// Changes of the matching status of a publisher or querier, as `true` when at least one
+/// subscriber or queryable matches and `false` when the last one went away.
+///
+/// The stream ends once the listener is undeclared or its publisher or querier dropped.
+pub struct MatchingStream {
+    listener: MatchingListener<()>,
+    receiver: HandlerReceiver<bool>,
+}
+
+impl MatchingStream {
+    /// Wait for the next change.
+    pub async fn recv_async(&self) -> ZResult<bool> {
+        self.receiver.recv_async().await
+    }
+
+    pub async fn undeclare(self) -> ZResult<()> {
+        self.listener.undeclare().await
+    }
+}
+
+impl Stream for MatchingStream {
+    type Item = bool;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<bool>> {
+        Pin::new(&mut self.receiver).poll_next(cx)
+    }
+}
+
+/// Matching status helpers shared by zenoh publishers and queriers.
+pub trait MatchingExt: Sync {
+    /// Whether any subscriber (for a publisher) or queryable (for a querier) matches now.
+    fn is_matching(&self) -> impl Future<Output = Result<bool, ZenohInterfaceError>> + Send;
+
+    fn matching_changed(
+        &self,
+    ) -> impl Future<Output = Result<MatchingStream, ZenohInterfaceError>> + Send;
+
+    /// Wait until something matches, returning `false` if nothing did within `timeout`.
+    fn wait_for_matching(
+        &self,
+        timeout: Duration,
+    ) -> impl Future<Output = Result<bool, ZenohInterfaceError>> + Send {
+        async move {
+            // Listen before checking, so a match declared in between is not missed.
+            let mut changes = self.matching_changed().await?;
+            if self.is_matching().await? {
+                return Ok(true);
+            }
+            let matched = changes.by_ref().any(|matching| async move { matching });
+            let matched = tokio::time::timeout(timeout, matched)
+                .await
+                .unwrap_or(false);
+            changes.undeclare().await?;
+            Ok(matched)
+        }
+    }
+}
+
+impl MatchingExt for Publisher<'static> {
+    async fn is_matching(&self) -> Result<bool, ZenohInterfaceError> {
+        Ok(self.matching_status().await?.matching())
+    }
+
+    async fn matching_changed(&self) -> Result<MatchingStream, ZenohInterfaceError> {
+        let (callback, receiver) = HandlerReceiver::new(&HandlerChannel::default());
+        let listener = self
+            .matching_listener()
+            .callback(move |status| callback.call(status.matching()))
+            .await?;
+        Ok(MatchingStream { listener, receiver })
+    }
+}
+
+impl MatchingExt for Querier<'static> {
+    async fn is_matching(&self) -> Result<bool, ZenohInterfaceError> {
+        Ok(self.matching_status().await?.matching())
+    }
+
+    async fn matching_changed(&self) -> Result<MatchingStream, ZenohInterfaceError> {
+        let (callback, receiver) = HandlerReceiver::new(&HandlerChannel::default());
+        let listener = self
+            .matching_listener()
+            .callback(move |status| callback.call(status.matching()))
+            .await?;
+        Ok(MatchingStream { listener, receiver })
+    }
+}
+
+struct BufferState {
+    matched: bool,
+    capacity: usize,
+    pending: VecDeque<(ZBytes, Option<ZBytes>)>,
+}
+
+/// Holds samples put while a publisher has no matching subscriber, and flushes them in
+/// order from a background task once one appears. The oldest samples are dropped when
+/// more than `capacity` are waiting.
+pub(crate) struct MatchingBuffer {
+    state: Arc<Mutex<BufferState>>,
+    chunker: Option<Chunker>,
+    task: JoinHandle<()>,
+}
+
+impl MatchingBuffer {
+    pub(crate) async fn new(
+        publisher: Arc<Publisher<'static>>,
+        capacity: usize,
+        chunker: Option<Chunker>,
+    ) -> Result<Self, ZenohInterfaceError> {
+        let mut changes = publisher.matching_changed().await?;
+        let state = Arc::new(Mutex::new(BufferState {
+            matched: publisher.is_matching().await?,
+            capacity,
+            pending: VecDeque::new(),
+        }));
+        let task = tokio::spawn({
+            let state = state.clone();
+            let chunker = chunker.clone();
+            async move {
+                while let Some(matching) = changes.next().await {
+                    // Flushing under the lock keeps concurrent `put`s behind the backlog.
+                    let mut state = state.lock().await;
+                    state.matched = matching;
+                    if !matching {
+                        continue;
+                    }
+                    while let Some((payload, attachment)) = state.pending.pop_front() {
+                        let sent = chunk::put(&publisher, chunker.as_ref(), payload, attachment);
+                        if let Err(e) = sent.await {
+                            eprintln!("Failed to flush buffered sample: {e}");
+                        }
+                    }
+                }
+            }
+        });
+        Ok(Self {
+            state,
+            chunker,
+            task,
+        })
+    }
+
+    /// Put the sample now if the publisher has a match, buffer it otherwise.
+    pub(crate) async fn put(
+        &self,
+        publisher: &Publisher<'static>,
+        payload: ZBytes,
+        attachment: Option<ZBytes>,
+    ) -> ZResult<()> {
+        let mut state = self.state.lock().await;
+        if state.matched {
+            return chunk::put(publisher, self.chunker.as_ref(), payload, attachment).await;
+        }
+        if state.pending.len() >= state.capacity {
+            state.pending.pop_front();
+        }
+        state.pending.push_back((payload, attachment));
+        Ok(())
+    }
+
+    /// Number of samples waiting for a match.
+    pub(crate) async fn pending(&self) -> usize {
+        self.state.lock().await.pending.len()
+    }
+}
+
+impl Drop for MatchingBuffer {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+pub(crate) async fn put(
+    publisher: &Publisher<'static>,
+    payload: ZBytes,
+    attachment: Option<ZBytes>,
+) -> ZResult<()> {
+    let mut put = publisher.put(payload);
+    if let Some(attachment) = attachment {
+        put = put.attachment(attachment);
+    }
+    put.await
+}
@@ -0,0 +1,141 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+use zenoh::bytes::ZBytes;
+use zenoh::sample::Sample;
+
+/// Metadata typed publishers attach to every sample, encoded as a JSON attachment.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct MessageMetadata {
+    /// Sequence number of the sample within its publisher, starting at 0.
+    pub seq: u64,
+    /// Publish time in nanoseconds since the Unix epoch.
+    pub timestamp_ns: u64,
+    pub message_type: String,
+    pub encoding: String,
+    pub deployed_application_id: String,
+    /// Identifies the publisher whose sequence `seq` belongs to.
+    pub publisher_id: String,
+}
+
+impl MessageMetadata {
+    pub fn to_attachment(&self) -> Result<ZBytes, serde_json::Error> {
+        Ok(serde_json::to_vec(self)?.into())
+    }
+
+    /// Metadata attached to `sample`, or `None` if it carries no make87 metadata.
+    pub fn from_sample(sample: &Sample) -> Option<Self> {
+        let attachment = sample.attachment()?;
+        serde_json::from_slice(&attachment.to_bytes()).ok()
+    }
+}
+
+/// Produces the [`MessageMetadata`] for consecutive samples of one publisher.
+#[derive(Debug)]
+pub struct MetadataStamper {
+    message_type: String,
+    encoding: String,
+    deployed_application_id: String,
+    publisher_id: String,
+    next_seq: AtomicU64,
+}
+
+impl MetadataStamper {
+    pub fn new(
+        message_type: &str,
+        encoding: &str,
+        deployed_application_id: &str,
+        publisher_id: &str,
+    ) -> Self {
+        Self {
+            message_type: message_type.to_string(),
+            encoding: encoding.to_string(),
+            deployed_application_id: deployed_application_id.to_string(),
+            publisher_id: publisher_id.to_string(),
+            next_seq: AtomicU64::new(0),
+        }
+    }
+
+    /// Metadata for the next sample, advancing the sequence number.
+    pub fn next(&self) -> MessageMetadata {
+        let timestamp_ns = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or_default();
+        MessageMetadata {
+            seq: self.next_seq.fetch_add(1, Ordering::Relaxed),
+            timestamp_ns,
+            message_type: self.message_type.clone(),
+            encoding: self.encoding.clone(),
+            deployed_application_id: self.deployed_application_id.clone(),
+            publisher_id: self.publisher_id.clone(),
+        }
+    }
+}
+
+/// Detects lost samples from the sequence numbers of each publisher on a topic.
+#[derive(Debug, Default)]
+pub struct GapDetector {
+    last_seq: Mutex<HashMap<String, u64>>,
+    missed: AtomicU64,
+}
+
+impl GapDetector {
+    /// Record `metadata` and return how many samples of its publisher were skipped since
+    /// the previous one. A sequence going backwards is taken as a restarted publisher.
+    pub fn observe(&self, metadata: &MessageMetadata) -> u64 {
+        let mut last_seq = self.last_seq.lock().unwrap_or_else(|e| e.into_inner());
+        let missed = match last_seq.insert(metadata.publisher_id.clone(), metadata.seq) {
+            Some(last) if metadata.seq > last => metadata.seq - last - 1,
+            _ => 0,
+        };
+        self.missed.fetch_add(missed, Ordering::Relaxed);
+        missed
+    }
+
+    /// Total number of samples missed across all publishers.
+    pub fn missed_total(&self) -> u64 {
+        self.missed.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stamper_sequence() {
+        let stamper = MetadataStamper::new("PlainText", "proto", "app-1", "pub-1");
+        let first = stamper.next();
+        let second = stamper.next();
+        assert_eq!((first.seq, second.seq), (0, 1));
+        assert!(second.timestamp_ns >= first.timestamp_ns);
+        assert_eq!(first.message_type, "PlainText");
+        assert_eq!(first.deployed_application_id, "app-1");
+
+        let attachment = first.to_attachment().unwrap();
+        let decoded: MessageMetadata = serde_json::from_slice(&attachment.to_bytes()).unwrap();
+        assert_eq!(decoded, first);
+    }
+
+    #[test]
+    fn test_gap_detector() {
+        let a = MetadataStamper::new("T", "json", "app", "a");
+        let b = MetadataStamper::new("T", "json", "app", "b");
+        let detector = GapDetector::default();
+
+        assert_eq!(detector.observe(&a.next()), 0);
+        assert_eq!(detector.observe(&b.next()), 0);
+        a.next();
+        a.next();
+        assert_eq!(detector.observe(&a.next()), 2);
+        assert_eq!(detector.observe(&b.next()), 0);
+        assert_eq!(detector.missed_total(), 2);
+
+        let restarted = MetadataStamper::new("T", "json", "app", "a");
+        assert_eq!(detector.observe(&restarted.next()), 0);
+        assert_eq!(detector.observe(&restarted.next()), 0);
+    }
+}
@@ -1,5 +1,30 @@
+mod bag;
+mod chunk;
+mod downsample;
+mod handler;
+mod history;
 mod interface;
+mod liveliness;
+mod matching;
+mod metadata;
 mod model;
+mod rpc;
+mod shm;
+mod sync;
+mod typed;
+mod validation;
 
+pub use bag::*;
+pub use chunk::*;
+pub use handler::*;
+pub use history::*;
 pub use interface::*;
+pub use liveliness::*;
+pub use matching::*;
+pub use metadata::*;
 pub use model::*;
+pub use rpc::*;
+pub use shm::*;
+pub use sync::*;
+pub use typed::*;
+pub use validation::*;
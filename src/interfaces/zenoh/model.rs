@@ -1,5 +1,7 @@
+use crate::models::AccessPoint;
 use serde::{Deserialize, Serialize};
-use zenoh::qos;
+use std::time::Duration;
+use zenoh::{qos, query};
 
 #[derive(Clone, Default, Debug, Serialize, Deserialize, PartialEq, Hash)]
 #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
@@ -63,6 +65,69 @@ impl CongestionControl {
     }
 }
 
+#[derive(Clone, Copy, Default, Debug, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum QueryTarget {
+    #[default]
+    BestMatching,
+    All,
+    AllComplete,
+}
+
+impl QueryTarget {
+    pub fn to_zenoh(&self) -> query::QueryTarget {
+        match self {
+            QueryTarget::BestMatching => query::QueryTarget::BestMatching,
+            QueryTarget::All => query::QueryTarget::All,
+            QueryTarget::AllComplete => query::QueryTarget::AllComplete,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Default, Debug, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ConsolidationMode {
+    #[default]
+    Auto,
+    None,
+    Monotonic,
+    Latest,
+}
+
+impl ConsolidationMode {
+    pub fn to_zenoh(&self) -> query::ConsolidationMode {
+        match self {
+            ConsolidationMode::Auto => query::ConsolidationMode::Auto,
+            ConsolidationMode::None => query::ConsolidationMode::None,
+            ConsolidationMode::Monotonic => query::ConsolidationMode::Monotonic,
+            ConsolidationMode::Latest => query::ConsolidationMode::Latest,
+        }
+    }
+}
+
+/// Which reply key expressions a querier accepts.
+#[derive(Clone, Copy, Default, Debug, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ReplyKeyExpr {
+    /// Accept replies on any key expression.
+    Any,
+    /// Accept only replies whose key expression matches the query.
+    #[default]
+    MatchingQuery,
+}
+
+impl ReplyKeyExpr {
+    pub fn to_zenoh(&self) -> query::ReplyKeyExpr {
+        match self {
+            ReplyKeyExpr::Any => query::ReplyKeyExpr::Any,
+            ReplyKeyExpr::MatchingQuery => query::ReplyKeyExpr::MatchingQuery,
+        }
+    }
+}
+
+/// Capacity of the default FIFO handler channel, matching zenoh's own default.
+pub const DEFAULT_HANDLER_CAPACITY: u32 = 256;
+
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Hash)]
 #[serde(tag = "handler_type")]
 #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
@@ -71,31 +136,350 @@ pub enum HandlerChannel {
     Ring { capacity: u32 },
 }
 
-#[derive(Debug, Serialize, Deserialize, PartialEq)]
+impl Default for HandlerChannel {
+    fn default() -> Self {
+        HandlerChannel::Fifo {
+            capacity: DEFAULT_HANDLER_CAPACITY,
+        }
+    }
+}
+
+/// Reassembly timeout used when a subscriber config does not set one.
+pub const DEFAULT_CHUNK_TIMEOUT_MS: u64 = 5_000;
+
+/// Defaults to a FIFO handler of [`DEFAULT_HANDLER_CAPACITY`], no downsampling, no
+/// history and a reassembly timeout of [`DEFAULT_CHUNK_TIMEOUT_MS`].
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
 pub struct ZenohSubscriberConfig {
     pub handler: HandlerChannel,
+    /// Drop samples arriving faster than this rate.
+    pub max_rate_hz: Option<f64>,
+    /// Keep only every n-th sample; `1` keeps all of them.
+    pub keep_every_nth: Option<u64>,
+    /// Drop samples arriving less than this long after the last one kept.
+    pub min_interval_ms: Option<u64>,
+    /// Ask the publishers' caches for their last samples when declared.
+    pub request_history: bool,
+    /// Discard chunked transfers not complete this long after their first chunk.
+    pub chunk_timeout_ms: Option<u64>,
 }
 
-#[derive(Debug, Serialize, Deserialize, PartialEq)]
+impl ZenohSubscriberConfig {
+    /// The stricter of `min_interval_ms` and the period of `max_rate_hz`, if any.
+    ///
+    /// A `max_rate_hz` that is not a positive number is ignored.
+    pub fn min_interval(&self) -> Option<Duration> {
+        let from_rate = self
+            .max_rate_hz
+            .filter(|hz| hz.is_finite() && *hz > 0.0)
+            .map(|hz| Duration::from_secs_f64(1.0 / hz));
+        let from_ms = self.min_interval_ms.map(Duration::from_millis);
+        from_rate.max(from_ms)
+    }
+
+    pub fn chunk_timeout(&self) -> Duration {
+        Duration::from_millis(self.chunk_timeout_ms.unwrap_or(DEFAULT_CHUNK_TIMEOUT_MS))
+    }
+
+    pub fn is_downsampled(&self) -> bool {
+        self.keep_every_nth.is_some_and(|n| n > 1) || self.min_interval().is_some()
+    }
+}
+
+/// Defaults to `DROP` congestion control, `DATA` priority, no express, `RELIABLE`
+/// reliability, no buffering, no history and no chunking.
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
 pub struct ZenohPublisherConfig {
     pub congestion_control: CongestionControl,
     pub priority: Priority,
     pub express: bool,
     pub reliability: Reliability,
+    /// Hold up to this many samples while no subscriber matches and send them once one
+    /// does. `0` disables buffering.
+    pub buffer_until_matched: usize,
+    /// Keep this many of the last samples for subscribers that request history. `0`
+    /// disables the cache.
+    pub history_depth: usize,
+    /// Split payloads larger than this many bytes into chunks. `0` disables chunking.
+    pub chunk_size: usize,
 }
 
-#[derive(Debug, Serialize, Deserialize, PartialEq)]
+/// Query timeout used when an endpoint config does not set one, matching zenoh's default.
+pub const DEFAULT_QUERY_TIMEOUT_MS: u64 = 10_000;
+
+/// Defaults to `DROP` congestion control, `DATA` priority, no express, a timeout of
+/// [`DEFAULT_QUERY_TIMEOUT_MS`], the `BEST_MATCHING` target, `AUTO` consolidation and
+/// accepting only replies matching the query.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
 pub struct ZenohQuerierConfig {
     pub congestion_control: CongestionControl,
     pub priority: Priority,
     pub express: bool,
+    pub timeout_ms: u64,
+    pub target: QueryTarget,
+    pub consolidation: ConsolidationMode,
+    pub accept_replies: ReplyKeyExpr,
 }
 
-#[derive(Debug, Serialize, Deserialize, PartialEq)]
+impl Default for ZenohQuerierConfig {
+    fn default() -> Self {
+        Self {
+            congestion_control: CongestionControl::default(),
+            priority: Priority::default(),
+            express: false,
+            timeout_ms: DEFAULT_QUERY_TIMEOUT_MS,
+            target: QueryTarget::default(),
+            consolidation: ConsolidationMode::default(),
+            accept_replies: ReplyKeyExpr::default(),
+        }
+    }
+}
+
+impl ZenohQuerierConfig {
+    pub fn timeout(&self) -> Duration {
+        Duration::from_millis(self.timeout_ms)
+    }
+}
+
+/// Defaults to a FIFO handler of [`DEFAULT_HANDLER_CAPACITY`].
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
 pub struct ZenohQueryableConfig {
     pub handler: HandlerChannel,
 }
 
+/// Default port a make87 zenoh session listens on.
+pub const DEFAULT_LISTEN_PORT: u16 = 7447;
+
+#[derive(Clone, Copy, Default, Debug, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ListenProtocol {
+    #[default]
+    Tcp,
+    Udp,
+    Quic,
+    Unixsock,
+}
+
+impl ListenProtocol {
+    /// Locator prefix zenoh uses for this protocol.
+    pub fn scheme(&self) -> &'static str {
+        match self {
+            ListenProtocol::Tcp => "tcp",
+            ListenProtocol::Udp => "udp",
+            ListenProtocol::Quic => "quic",
+            ListenProtocol::Unixsock => "unixsock-stream",
+        }
+    }
+}
+
+/// What to do when the session cannot listen on its configured endpoints.
+#[derive(Clone, Copy, Default, Debug, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ListenPolicy {
+    /// Fail to open the session.
+    Strict,
+    /// Retry once with every IP endpoint moved to an OS-assigned port. Endpoints without a
+    /// port, such as unix sockets, are dropped for the retry.
+    #[default]
+    Fallback,
+}
+
+/// Which address of a bound entity's access point the session connects to.
+#[derive(Clone, Copy, Default, Debug, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ConnectPolicy {
+    /// Always use the VPN address.
+    #[default]
+    Vpn,
+    /// Use the public address when the access point has one.
+    Public,
+    /// Use the VPN address for peers on the same node, the public address otherwise.
+    Auto,
+}
+
+impl ConnectPolicy {
+    /// Zenoh TCP locator for `access_point` under this policy.
+    pub fn locator(&self, access_point: &AccessPoint) -> String {
+        let public = match (&access_point.public_ip, access_point.public_port) {
+            (Some(ip), Some(port)) => Some((ip.as_str(), port)),
+            _ => None,
+        };
+        let use_public = match self {
+            ConnectPolicy::Vpn => false,
+            ConnectPolicy::Public => true,
+            ConnectPolicy::Auto => !access_point.same_node,
+        };
+        let (ip, port) = public
+            .filter(|_| use_public)
+            .unwrap_or((access_point.vpn_ip.as_str(), access_point.vpn_port));
+        format!("tcp/{}:{}", format_host(ip), port)
+    }
+}
+
+fn default_listen_host() -> String {
+    "0.0.0.0".to_string()
+}
+
+fn default_listen_port() -> u16 {
+    DEFAULT_LISTEN_PORT
+}
+
+fn default_multicast_scouting() -> bool {
+    true
+}
+
+fn default_shm_pool_size() -> usize {
+    32 * 1024 * 1024
+}
+
+fn default_shm_alignment() -> usize {
+    8
+}
+
+fn default_shm_min_payload_size() -> usize {
+    64 * 1024
+}
+
+/// Shared-memory settings for publishers on this interface.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct ZenohShmConfig {
+    /// Size of the shared-memory pool in bytes.
+    #[serde(default = "default_shm_pool_size")]
+    pub pool_size: usize,
+    /// Alignment of each buffer in bytes. Must be a power of two.
+    #[serde(default = "default_shm_alignment")]
+    pub alignment: usize,
+    /// Payloads smaller than this are published as regular payloads.
+    #[serde(default = "default_shm_min_payload_size")]
+    pub min_payload_size: usize,
+}
+
+impl Default for ZenohShmConfig {
+    fn default() -> Self {
+        Self {
+            pool_size: default_shm_pool_size(),
+            alignment: default_shm_alignment(),
+            min_payload_size: default_shm_min_payload_size(),
+        }
+    }
+}
+
+impl ZenohShmConfig {
+    /// Alignment as the power of two zenoh expects, or `None` if it is not a power of two.
+    pub fn alignment_pow(&self) -> Option<u8> {
+        self.alignment
+            .is_power_of_two()
+            .then(|| self.alignment.trailing_zeros() as u8)
+    }
+}
+
+/// Interface-level zenoh settings, read from the keys of the interface config.
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct ZenohInterfaceConfig {
+    /// Explicit zenoh locators to listen on. Takes precedence over protocol, host and port;
+    /// an empty list disables listening.
+    #[serde(default)]
+    pub listen_endpoints: Option<Vec<String>>,
+    #[serde(default)]
+    pub listen_protocol: ListenProtocol,
+    #[serde(default = "default_listen_host")]
+    pub listen_host: String,
+    #[serde(default = "default_listen_port")]
+    pub listen_port: u16,
+    /// Socket path for the `UNIXSOCK` protocol.
+    #[serde(default)]
+    pub listen_path: Option<String>,
+    #[serde(default)]
+    pub listen_policy: ListenPolicy,
+    #[serde(default)]
+    pub connect_policy: ConnectPolicy,
+    /// Explicit zenoh locators to connect to. Takes precedence over the access points of
+    /// bound entities; an empty list disables connecting.
+    #[serde(default)]
+    pub connect_endpoints: Option<Vec<String>>,
+    /// Discover peers through multicast scouting.
+    #[serde(default = "default_multicast_scouting")]
+    pub multicast_scouting: bool,
+    /// Enables shared-memory publishing when set.
+    #[serde(default)]
+    pub shm: Option<ZenohShmConfig>,
+}
+
+impl Default for ZenohInterfaceConfig {
+    fn default() -> Self {
+        Self {
+            listen_endpoints: None,
+            listen_protocol: ListenProtocol::default(),
+            listen_host: default_listen_host(),
+            listen_port: default_listen_port(),
+            listen_path: None,
+            listen_policy: ListenPolicy::default(),
+            connect_policy: ConnectPolicy::default(),
+            connect_endpoints: None,
+            multicast_scouting: default_multicast_scouting(),
+            shm: None,
+        }
+    }
+}
+
+impl ZenohInterfaceConfig {
+    /// Zenoh locators the session should listen on.
+    ///
+    /// A `UNIXSOCK` protocol without `listen_path` yields no endpoint.
+    pub fn listen_endpoints(&self) -> Vec<String> {
+        if let Some(endpoints) = &self.listen_endpoints {
+            return endpoints.clone();
+        }
+        let scheme = self.listen_protocol.scheme();
+        match self.listen_protocol {
+            ListenProtocol::Unixsock => self
+                .listen_path
+                .iter()
+                .map(|path| format!("{}/{}", scheme, path))
+                .collect(),
+            _ => vec![format!(
+                "{}/{}:{}",
+                scheme,
+                format_host(&self.listen_host),
+                self.listen_port
+            )],
+        }
+    }
+
+    /// Endpoints used for the retry under [`ListenPolicy::Fallback`].
+    pub fn fallback_listen_endpoints(&self) -> Vec<String> {
+        self.listen_endpoints()
+            .iter()
+            .filter_map(|endpoint| with_ephemeral_port(endpoint))
+            .collect()
+    }
+}
+
+fn format_host(host: &str) -> String {
+    if host.contains(':') && !host.starts_with('[') {
+        format!("[{}]", host)
+    } else {
+        host.to_string()
+    }
+}
+
+/// Rewrite `proto/host:port[?cfg][#meta]` to use port 0, or `None` if it has no port.
+fn with_ephemeral_port(endpoint: &str) -> Option<String> {
+    let suffix_start = endpoint.find(['?', '#']).unwrap_or(endpoint.len());
+    let (locator, suffix) = endpoint.split_at(suffix_start);
+    let (scheme, address) = locator.split_once('/')?;
+    let (host, port) = address.rsplit_once(':')?;
+    port.parse::<u16>().ok()?;
+    if host.is_empty() || (host.contains(':') && !host.ends_with(']')) {
+        return None;
+    }
+    Some(format!("{}/{}:0{}", scheme, host, suffix))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -119,6 +503,20 @@ mod tests {
         assert_eq!(CongestionControl::Block.to_zenoh(), zenoh::qos::CongestionControl::Block);
     }
 
+    #[test]
+    fn test_query_options_to_zenoh() {
+        assert_eq!(QueryTarget::All.to_zenoh(), zenoh::query::QueryTarget::All);
+        assert_eq!(
+            ConsolidationMode::None.to_zenoh(),
+            zenoh::query::ConsolidationMode::None
+        );
+        assert_eq!(ReplyKeyExpr::Any.to_zenoh(), zenoh::query::ReplyKeyExpr::Any);
+        assert_eq!(
+            serde_json::to_string(&QueryTarget::AllComplete).unwrap(),
+            "\"ALL_COMPLETE\""
+        );
+    }
+
     #[test]
     fn test_handler_channel_serialization() {
         let fifo = HandlerChannel::Fifo { capacity: 10 };
@@ -137,12 +535,35 @@ mod tests {
     fn test_zenoh_subscriber_config_serialization() {
         let config = ZenohSubscriberConfig {
             handler: HandlerChannel::Fifo { capacity: 3 },
+            max_rate_hz: Some(1.0),
+            keep_every_nth: Some(30),
+            min_interval_ms: None,
+            request_history: true,
+            chunk_timeout_ms: Some(250),
         };
         let json = serde_json::to_string(&config).unwrap();
         let de: ZenohSubscriberConfig = serde_json::from_str(&json).unwrap();
         assert_eq!(config, de);
     }
 
+    #[test]
+    fn test_zenoh_subscriber_min_interval() {
+        let config: ZenohSubscriberConfig = serde_json::from_str("{}").unwrap();
+        assert_eq!(config.min_interval(), None);
+        assert!(!config.is_downsampled());
+
+        let config: ZenohSubscriberConfig =
+            serde_json::from_str(r#"{"max_rate_hz": 4.0, "min_interval_ms": 100}"#).unwrap();
+        assert_eq!(config.min_interval(), Some(Duration::from_millis(250)));
+        let config: ZenohSubscriberConfig =
+            serde_json::from_str(r#"{"max_rate_hz": 0.0, "min_interval_ms": 100}"#).unwrap();
+        assert_eq!(config.min_interval(), Some(Duration::from_millis(100)));
+
+        let config: ZenohSubscriberConfig =
+            serde_json::from_str(r#"{"keep_every_nth": 1}"#).unwrap();
+        assert!(!config.is_downsampled());
+    }
+
     #[test]
     fn test_zenoh_publisher_config_serialization() {
         let config = ZenohPublisherConfig {
@@ -150,6 +571,9 @@ mod tests {
             priority: Priority::InteractiveHigh,
             express: true,
             reliability: Reliability::BestEffort,
+            buffer_until_matched: 16,
+            history_depth: 1,
+            chunk_size: 65_536,
         };
         let json = serde_json::to_string(&config).unwrap();
         let de: ZenohPublisherConfig = serde_json::from_str(&json).unwrap();
@@ -162,12 +586,131 @@ mod tests {
             congestion_control: CongestionControl::Drop,
             priority: Priority::Data,
             express: false,
+            timeout_ms: 250,
+            target: QueryTarget::AllComplete,
+            consolidation: ConsolidationMode::Latest,
+            accept_replies: ReplyKeyExpr::Any,
         };
         let json = serde_json::to_string(&config).unwrap();
         let de: ZenohQuerierConfig = serde_json::from_str(&json).unwrap();
         assert_eq!(config, de);
     }
 
+    #[test]
+    fn test_entity_config_defaults() {
+        let publisher: ZenohPublisherConfig = serde_json::from_str(r#"{"express": true}"#).unwrap();
+        assert_eq!(
+            publisher,
+            ZenohPublisherConfig {
+                express: true,
+                ..Default::default()
+            }
+        );
+        assert_eq!(publisher.congestion_control, CongestionControl::Drop);
+        assert_eq!(publisher.reliability, Reliability::Reliable);
+        assert_eq!(publisher.buffer_until_matched, 0);
+        assert_eq!(publisher.history_depth, 0);
+        assert_eq!(publisher.chunk_size, 0);
+
+        let subscriber: ZenohSubscriberConfig = serde_json::from_str("{}").unwrap();
+        assert_eq!(subscriber.handler, HandlerChannel::Fifo { capacity: 256 });
+        assert!(!subscriber.request_history);
+        assert_eq!(subscriber.chunk_timeout(), Duration::from_secs(5));
+        let querier: ZenohQuerierConfig = serde_json::from_str("{}").unwrap();
+        assert_eq!(querier, ZenohQuerierConfig::default());
+        assert_eq!(querier.timeout(), Duration::from_secs(10));
+        assert_eq!(querier.target, QueryTarget::BestMatching);
+        let queryable: ZenohQueryableConfig = serde_json::from_str("{}").unwrap();
+        assert_eq!(queryable, ZenohQueryableConfig::default());
+    }
+
+    #[test]
+    fn test_zenoh_interface_config_defaults() {
+        let config: ZenohInterfaceConfig = serde_json::from_str("{}").unwrap();
+        assert_eq!(config, ZenohInterfaceConfig::default());
+        assert_eq!(config.listen_endpoints(), vec!["tcp/0.0.0.0:7447"]);
+        assert_eq!(config.fallback_listen_endpoints(), vec!["tcp/0.0.0.0:0"]);
+        assert!(config.connect_endpoints.is_none());
+        assert!(config.multicast_scouting);
+    }
+
+    #[test]
+    fn test_zenoh_interface_config_listen_endpoints() {
+        let config: ZenohInterfaceConfig = serde_json::from_value(serde_json::json!({
+            "listen_protocol": "QUIC",
+            "listen_host": "::",
+            "listen_port": 7500,
+            "listen_policy": "STRICT"
+        }))
+        .unwrap();
+        assert_eq!(config.listen_policy, ListenPolicy::Strict);
+        assert_eq!(config.listen_endpoints(), vec!["quic/[::]:7500"]);
+
+        let config: ZenohInterfaceConfig = serde_json::from_value(serde_json::json!({
+            "listen_protocol": "UNIXSOCK",
+            "listen_path": "/tmp/app.sock"
+        }))
+        .unwrap();
+        assert_eq!(config.listen_endpoints(), vec!["unixsock-stream//tmp/app.sock"]);
+        assert!(config.fallback_listen_endpoints().is_empty());
+
+        let config: ZenohInterfaceConfig = serde_json::from_value(serde_json::json!({
+            "listen_endpoints": ["tcp/127.0.0.1:7447#iface=lo", "udp/[::1]:7448"],
+            "listen_port": 9000
+        }))
+        .unwrap();
+        assert_eq!(
+            config.listen_endpoints(),
+            vec!["tcp/127.0.0.1:7447#iface=lo", "udp/[::1]:7448"]
+        );
+        assert_eq!(
+            config.fallback_listen_endpoints(),
+            vec!["tcp/127.0.0.1:0#iface=lo", "udp/[::1]:0"]
+        );
+    }
+
+    #[test]
+    fn test_zenoh_shm_config() {
+        let config: ZenohInterfaceConfig =
+            serde_json::from_value(serde_json::json!({"shm": {"pool_size": 1048576}})).unwrap();
+        let shm = config.shm.unwrap();
+        assert_eq!(shm.pool_size, 1048576);
+        assert_eq!(shm.alignment, 8);
+        assert_eq!(shm.alignment_pow(), Some(3));
+
+        let shm = ZenohShmConfig {
+            alignment: 12,
+            ..Default::default()
+        };
+        assert_eq!(shm.alignment_pow(), None);
+    }
+
+    #[test]
+    fn test_connect_policy_locator() {
+        let remote = AccessPoint {
+            vpn_ip: "10.0.0.2".into(),
+            vpn_port: 7447,
+            public_ip: Some("203.0.113.5".into()),
+            public_port: Some(17447),
+            same_node: false,
+        };
+        let local = AccessPoint {
+            same_node: true,
+            ..remote.clone()
+        };
+        let vpn_only = AccessPoint {
+            public_ip: None,
+            public_port: None,
+            ..remote.clone()
+        };
+
+        assert_eq!(ConnectPolicy::Vpn.locator(&remote), "tcp/10.0.0.2:7447");
+        assert_eq!(ConnectPolicy::Public.locator(&remote), "tcp/203.0.113.5:17447");
+        assert_eq!(ConnectPolicy::Public.locator(&vpn_only), "tcp/10.0.0.2:7447");
+        assert_eq!(ConnectPolicy::Auto.locator(&remote), "tcp/203.0.113.5:17447");
+        assert_eq!(ConnectPolicy::Auto.locator(&local), "tcp/10.0.0.2:7447");
+    }
+
     #[test]
     fn test_zenoh_queryable_config_serialization() {
         let config = ZenohQueryableConfig {
@@ -0,0 +1,379 @@
+use crate::encodings::{BoxedEncoder, Encoder};
+use crate::interfaces::zenoh::{
+    ConfiguredQueryable, ConsolidationMode, MatchingExt, MatchingStream, QueryTarget, ReplyKeyExpr,
+    ZenohInterfaceError, ZenohQuerierConfig,
+};
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::time::{Duration, Instant};
+use zenoh::bytes::Encoding;
+use zenoh::query::{Querier, Query};
+use zenoh::Session;
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum RpcErrorKind {
+    /// The provider could not decode the request payload.
+    InvalidRequest,
+    /// The provider's handler returned an error.
+    Handler,
+    /// The provider failed to encode its response.
+    Internal,
+    /// The error reply did not carry a make87 error payload.
+    Unknown,
+}
+
+/// Structured error sent by a typed provider through `reply_err`, and decoded back by
+/// [`TypedQuerier`] on the requester side.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, thiserror::Error)]
+#[error("{kind:?}: {message}")]
+pub struct RpcError {
+    pub kind: RpcErrorKind,
+    pub message: String,
+}
+
+impl RpcError {
+    pub fn new(kind: RpcErrorKind, message: impl Into<String>) -> Self {
+        Self {
+            kind,
+            message: message.into(),
+        }
+    }
+
+    /// An error raised by a provider's request handler.
+    pub fn handler(message: impl Into<String>) -> Self {
+        Self::new(RpcErrorKind::Handler, message)
+    }
+
+    fn to_payload(&self) -> Vec<u8> {
+        serde_json::to_vec(self).unwrap_or_else(|_| self.message.clone().into_bytes())
+    }
+
+    fn from_payload(payload: &[u8]) -> Self {
+        serde_json::from_slice(payload).unwrap_or_else(|_| {
+            Self::new(
+                RpcErrorKind::Unknown,
+                String::from_utf8_lossy(payload).into_owned(),
+            )
+        })
+    }
+}
+
+/// Per-call overrides of the querier settings from [`ZenohQuerierConfig`].
+///
+/// Unset fields keep the value configured for the endpoint.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CallOptions {
+    pub timeout: Option<Duration>,
+    pub target: Option<QueryTarget>,
+    pub consolidation: Option<ConsolidationMode>,
+    pub accept_replies: Option<ReplyKeyExpr>,
+}
+
+impl CallOptions {
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn target(mut self, target: QueryTarget) -> Self {
+        self.target = Some(target);
+        self
+    }
+
+    pub fn consolidation(mut self, consolidation: ConsolidationMode) -> Self {
+        self.consolidation = Some(consolidation);
+        self
+    }
+
+    pub fn accept_replies(mut self, accept_replies: ReplyKeyExpr) -> Self {
+        self.accept_replies = Some(accept_replies);
+        self
+    }
+
+    fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Fields set in `self`, falling back to those set in `other`.
+    fn or(&self, other: &Self) -> Self {
+        Self {
+            timeout: self.timeout.or(other.timeout),
+            target: self.target.or(other.target),
+            consolidation: self.consolidation.or(other.consolidation),
+            accept_replies: self.accept_replies.or(other.accept_replies),
+        }
+    }
+}
+
+/// A zenoh querier that sends `Req` values and decodes `Resp` replies.
+pub struct TypedQuerier<Req, Resp> {
+    session: Session,
+    querier: Querier<'static>,
+    config: ZenohQuerierConfig,
+    request_encoder: BoxedEncoder<Req>,
+    response_encoder: BoxedEncoder<Resp>,
+    defaults: CallOptions,
+}
+
+impl<Req, Resp> TypedQuerier<Req, Resp> {
+    /// `config` must be the one `querier` was declared with; it supplies the settings that
+    /// [`CallOptions`] do not override.
+    pub fn new(
+        session: &Session,
+        querier: Querier<'static>,
+        config: ZenohQuerierConfig,
+        request_encoder: BoxedEncoder<Req>,
+        response_encoder: BoxedEncoder<Resp>,
+    ) -> Self {
+        Self {
+            session: session.clone(),
+            querier,
+            config,
+            request_encoder,
+            response_encoder,
+            defaults: CallOptions::default(),
+        }
+    }
+
+    /// Apply `options` to every call that does not override them itself.
+    pub fn with_options(mut self, options: CallOptions) -> Self {
+        self.defaults = options;
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.defaults.timeout = Some(timeout);
+        self
+    }
+
+    pub fn timeout(&self) -> Duration {
+        self.defaults
+            .timeout
+            .unwrap_or_else(|| self.config.timeout())
+    }
+
+    /// Send `request` and wait for the first reply, up to the querier's timeout.
+    ///
+    /// An error reply from the provider is returned as [`ZenohInterfaceError::Rpc`].
+    pub async fn call(&self, request: &Req) -> Result<Resp, ZenohInterfaceError> {
+        self.call_with(request, &CallOptions::default()).await
+    }
+
+    pub async fn call_with_timeout(
+        &self,
+        request: &Req,
+        timeout: Duration,
+    ) -> Result<Resp, ZenohInterfaceError> {
+        self.call_with(request, &CallOptions::default().timeout(timeout))
+            .await
+    }
+
+    /// Like [`TypedQuerier::call`], overriding the endpoint's querier settings for this call.
+    pub async fn call_with(
+        &self,
+        request: &Req,
+        options: &CallOptions,
+    ) -> Result<Resp, ZenohInterfaceError> {
+        let payload = self
+            .request_encoder
+            .encode(request)
+            .map_err(ZenohInterfaceError::Encode)?;
+        let options = options.or(&self.defaults);
+        let timeout = options.timeout.unwrap_or_else(|| self.config.timeout());
+        let key_expr = self.querier.key_expr();
+
+        let started = Instant::now();
+        // The declared querier already carries the configured settings; overrides need a
+        // one-off session query.
+        let replies = if options.is_empty() {
+            self.querier.get().payload(payload).await?
+        } else {
+            self.session
+                .get(key_expr)
+                .payload(payload)
+                .congestion_control(self.config.congestion_control.to_zenoh())
+                .priority(self.config.priority.to_zenoh())
+                .express(self.config.express)
+                .timeout(timeout)
+                .target(options.target.unwrap_or(self.config.target).to_zenoh())
+                .consolidation(
+                    options
+                        .consolidation
+                        .unwrap_or(self.config.consolidation)
+                        .to_zenoh(),
+                )
+                .accept_replies(
+                    options
+                        .accept_replies
+                        .unwrap_or(self.config.accept_replies)
+                        .to_zenoh(),
+                )
+                .await?
+        };
+        // zenoh closes the reply channel once the query is finalized, including on timeout.
+        let reply = replies.recv_async().await.map_err(|_| {
+            if started.elapsed() >= timeout {
+                ZenohInterfaceError::Timeout(key_expr.to_string())
+            } else {
+                ZenohInterfaceError::NoReply(key_expr.to_string())
+            }
+        })?;
+
+        match reply.result() {
+            Ok(sample) => self
+                .response_encoder
+                .decode(&sample.payload().to_bytes())
+                .map_err(ZenohInterfaceError::Decode),
+            // zenoh answers a query that ran out of time with a plain "Timeout" error reply.
+            Err(err)
+                if *err.encoding() != Encoding::APPLICATION_JSON
+                    && err.payload().to_bytes().as_ref() == b"Timeout" =>
+            {
+                Err(ZenohInterfaceError::Timeout(key_expr.to_string()))
+            }
+            Err(err) => Err(ZenohInterfaceError::Rpc(RpcError::from_payload(
+                &err.payload().to_bytes(),
+            ))),
+        }
+    }
+
+    pub async fn is_matching(&self) -> Result<bool, ZenohInterfaceError> {
+        self.querier.is_matching().await
+    }
+
+    /// Wait until a queryable matches, returning `false` if none did within `timeout`.
+    pub async fn wait_for_matching(&self, timeout: Duration) -> Result<bool, ZenohInterfaceError> {
+        self.querier.wait_for_matching(timeout).await
+    }
+
+    pub async fn matching_changed(&self) -> Result<MatchingStream, ZenohInterfaceError> {
+        self.querier.matching_changed().await
+    }
+
+    pub fn querier(&self) -> &Querier<'static> {
+        &self.querier
+    }
+
+    pub fn config(&self) -> &ZenohQuerierConfig {
+        &self.config
+    }
+}
+
+/// A configured queryable that decodes `Req` queries and answers them with `Resp` replies.
+pub struct TypedQueryable<Req, Resp> {
+    queryable: ConfiguredQueryable,
+    request_encoder: BoxedEncoder<Req>,
+    response_encoder: BoxedEncoder<Resp>,
+}
+
+impl<Req, Resp> TypedQueryable<Req, Resp> {
+    pub fn new(
+        queryable: ConfiguredQueryable,
+        request_encoder: BoxedEncoder<Req>,
+        response_encoder: BoxedEncoder<Resp>,
+    ) -> Self {
+        Self {
+            queryable,
+            request_encoder,
+            response_encoder,
+        }
+    }
+
+    /// Answer incoming queries with `handler` until the queryable is undeclared.
+    ///
+    /// Requests that fail to decode, handler errors and responses that fail to encode are
+    /// all answered with `reply_err` carrying an [`RpcError`]. A reply that cannot be sent,
+    /// e.g. because the querier gave up waiting, is logged and serving goes on.
+    ///
+    /// Queries are handled one at a time, so a slow handler holds up every query behind
+    /// it; use [`serve_concurrently`](Self::serve_concurrently) to overlap them.
+    pub async fn serve<F, Fut>(&self, handler: F) -> Result<(), ZenohInterfaceError>
+    where
+        F: Fn(Req) -> Fut,
+        Fut: Future<Output = Result<Resp, RpcError>>,
+    {
+        self.serve_concurrently(1, handler).await
+    }
+
+    /// Like [`serve`](Self::serve), but handling up to `limit` queries at once.
+    pub async fn serve_concurrently<F, Fut>(
+        &self,
+        limit: usize,
+        handler: F,
+    ) -> Result<(), ZenohInterfaceError>
+    where
+        F: Fn(Req) -> Fut,
+        Fut: Future<Output = Result<Resp, RpcError>>,
+    {
+        let handler = &handler;
+        let queries = futures::stream::unfold(&self.queryable, |queryable| async move {
+            let query = queryable.recv_async().await.ok()?;
+            Some((query, queryable))
+        });
+        queries
+            .for_each_concurrent(limit.max(1), |query| async move {
+                if let Err(e) = self.handle(&query, handler).await {
+                    eprintln!("Failed to answer query on {}: {e}", query.key_expr());
+                }
+            })
+            .await;
+        Ok(())
+    }
+
+    /// Answer a single query with `handler`.
+    pub async fn handle<F, Fut>(&self, query: &Query, handler: F) -> Result<(), ZenohInterfaceError>
+    where
+        F: Fn(Req) -> Fut,
+        Fut: Future<Output = Result<Resp, RpcError>>,
+    {
+        let payload = query.payload().map(|p| p.to_bytes()).unwrap_or_default();
+        let result = match self.request_encoder.decode(&payload) {
+            Ok(request) => handler(request).await.and_then(|response| {
+                self.response_encoder
+                    .encode(&response)
+                    .map_err(|e| RpcError::new(RpcErrorKind::Internal, e.to_string()))
+            }),
+            Err(e) => {
+                self.queryable.record_decode_failure();
+                Err(RpcError::new(RpcErrorKind::InvalidRequest, e.to_string()))
+            }
+        };
+        match result {
+            Ok(response) => query.reply(query.key_expr().clone(), response).await?,
+            Err(err) => {
+                query
+                    .reply_err(err.to_payload())
+                    .encoding(Encoding::APPLICATION_JSON)
+                    .await?
+            }
+        }
+        Ok(())
+    }
+
+    pub fn queryable(&self) -> &ConfiguredQueryable {
+        &self.queryable
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rpc_error_payload_roundtrip() {
+        let err = RpcError::handler("division by zero");
+        let payload = err.to_payload();
+        let json: serde_json::Value = serde_json::from_slice(&payload).unwrap();
+        assert_eq!(json["kind"], "HANDLER");
+        assert_eq!(RpcError::from_payload(&payload), err);
+    }
+
+    #[test]
+    fn test_rpc_error_from_foreign_payload() {
+        let err = RpcError::from_payload(b"plain text failure");
+        assert_eq!(err.kind, RpcErrorKind::Unknown);
+        assert_eq!(err.message, "plain text failure");
+    }
+}
@@ -0,0 +1,238 @@
+use crate::encodings::Encoder;
+use crate::interfaces::zenoh::{
+    MetadataStamper, ZenohInterface, ZenohInterfaceError, ZenohShmConfig,
+};
+use std::marker::PhantomData;
+use std::sync::Arc;
+use zenoh::bytes::ZBytes;
+use zenoh::pubsub::Publisher;
+use zenoh::shm::{
+    AllocAlignment, GarbageCollect, MemoryLayout, PosixShmProviderBackend, ShmProvider,
+    ShmProviderBuilder, ZShmMut,
+};
+use zenoh::{Session, Wait};
+
+/// Shared-memory provider backed by a POSIX shared-memory pool.
+pub type ZenohShmProvider = ShmProvider<PosixShmProviderBackend>;
+
+/// Build a shared-memory pool as described by `config`.
+pub fn create_shm_provider(
+    config: &ZenohShmConfig,
+) -> Result<ZenohShmProvider, ZenohInterfaceError> {
+    let alignment = config
+        .alignment_pow()
+        .and_then(|pow| AllocAlignment::new(pow).ok())
+        .ok_or_else(|| {
+            ZenohInterfaceError::Shm(format!("invalid alignment {}", config.alignment))
+        })?;
+    let layout = MemoryLayout::new(config.pool_size, alignment).map_err(|e| {
+        ZenohInterfaceError::Shm(format!("invalid pool size {}: {:?}", config.pool_size, e))
+    })?;
+    let backend = PosixShmProviderBackend::builder(layout).wait()?;
+    Ok(ShmProviderBuilder::backend(backend).wait())
+}
+
+/// A zenoh publisher that encodes values of type `T` straight into shared-memory buffers.
+///
+/// Payloads go out as regular payloads when no provider is set, when they are smaller than
+/// the configured minimum, or when the pool has no room left. Zenoh itself sends shared
+/// memory buffers as regular payloads over links to peers on other hosts, so remote
+/// subscribers keep receiving every message.
+pub struct ShmPublisher<T, E: Encoder<T>> {
+    publisher: Publisher<'static>,
+    encoder: E,
+    provider: Option<Arc<ZenohShmProvider>>,
+    min_payload_size: usize,
+    metadata: Option<MetadataStamper>,
+    _marker: PhantomData<T>,
+}
+
+impl<T, E: Encoder<T>> ShmPublisher<T, E> {
+    pub fn new(
+        publisher: Publisher<'static>,
+        encoder: E,
+        provider: Option<Arc<ZenohShmProvider>>,
+        min_payload_size: usize,
+    ) -> Self {
+        Self {
+            publisher,
+            encoder,
+            provider,
+            min_payload_size,
+            metadata: None,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Attach metadata from `stamper` to every published sample.
+    pub fn with_metadata(mut self, stamper: MetadataStamper) -> Self {
+        self.metadata = Some(stamper);
+        self
+    }
+
+    /// Encode `value` and put it on the publisher's key expression.
+    ///
+    /// Encoders that know the encoded size up front write directly into the shared-memory
+    /// buffer; others are encoded first and copied over.
+    pub async fn publish(&self, value: &T) -> Result<(), ZenohInterfaceError> {
+        let payload: ZBytes = match self.encoder.encoded_len(value) {
+            Some(len) => match self.alloc(len) {
+                Some(mut buf) => {
+                    self.encoder
+                        .encode_into(value, &mut buf)
+                        .map_err(ZenohInterfaceError::Encode)?;
+                    buf.into()
+                }
+                None => self.encode(value)?.into(),
+            },
+            None => {
+                let bytes = self.encode(value)?;
+                match self.alloc(bytes.len()) {
+                    Some(mut buf) => {
+                        buf.copy_from_slice(&bytes);
+                        buf.into()
+                    }
+                    None => bytes.into(),
+                }
+            }
+        };
+        let mut put = self.publisher.put(payload);
+        if let Some(stamper) = &self.metadata {
+            put = put.attachment(stamper.next().to_attachment()?);
+        }
+        put.await?;
+        Ok(())
+    }
+
+    fn encode(&self, value: &T) -> Result<Vec<u8>, ZenohInterfaceError> {
+        self.encoder
+            .encode(value)
+            .map_err(ZenohInterfaceError::Encode)
+    }
+
+    /// Allocate a shared-memory buffer of exactly `len` bytes, if the payload should use one.
+    fn alloc(&self, len: usize) -> Option<ZShmMut> {
+        let provider = self.provider.as_ref()?;
+        if len == 0 || len < self.min_payload_size {
+            return None;
+        }
+        provider
+            .alloc(len)
+            .with_policy::<GarbageCollect>()
+            .wait()
+            .ok()
+    }
+
+    pub fn publisher(&self) -> &Publisher<'static> {
+        &self.publisher
+    }
+
+    pub fn encoder(&self) -> &E {
+        &self.encoder
+    }
+
+    pub fn provider(&self) -> Option<&Arc<ZenohShmProvider>> {
+        self.provider.as_ref()
+    }
+}
+
+impl ZenohInterface {
+    /// Build the shared-memory provider configured under the interface's `shm` key.
+    ///
+    /// Returns `None` when shared memory is not configured. Every call creates a new pool,
+    /// so share the provider between publishers.
+    pub fn get_shm_provider(&self) -> Result<Option<Arc<ZenohShmProvider>>, ZenohInterfaceError> {
+        self.zenoh_interface_config()?
+            .shm
+            .as_ref()
+            .map(|config| create_shm_provider(config).map(Arc::new))
+            .transpose()
+    }
+
+    /// Declare the publisher for `name` as a [`ShmPublisher`] allocating from `provider`.
+    pub async fn get_shm_publisher<T, E: Encoder<T>>(
+        &self,
+        session: &Session,
+        name: &str,
+        encoder: E,
+        provider: Option<Arc<ZenohShmProvider>>,
+    ) -> Result<ShmPublisher<T, E>, ZenohInterfaceError> {
+        let pub_cfg = self
+            .get_publisher_config(name)
+            .ok_or_else(|| ZenohInterfaceError::PubTopicNotFound(name.to_string()))?;
+        let min_payload_size = self
+            .zenoh_interface_config()?
+            .shm
+            .map(|config| config.min_payload_size)
+            .unwrap_or_default();
+        let publisher = self.get_publisher(session, name).await?;
+        let stamper = self.metadata_stamper(pub_cfg, &publisher);
+        Ok(
+            ShmPublisher::new(publisher, encoder, provider, min_payload_size)
+                .with_metadata(stamper),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encodings::JsonEncoder;
+    use crate::testing::AppFixture;
+    use serde_json::json;
+    use std::time::Duration;
+
+    #[test]
+    fn test_create_shm_provider_rejects_bad_layout() {
+        let config = ZenohShmConfig {
+            alignment: 12,
+            ..Default::default()
+        };
+        assert!(matches!(
+            create_shm_provider(&config),
+            Err(ZenohInterfaceError::Shm(_))
+        ));
+        let config = ZenohShmConfig {
+            pool_size: 0,
+            ..Default::default()
+        };
+        assert!(matches!(
+            create_shm_provider(&config),
+            Err(ZenohInterfaceError::Shm(_))
+        ));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn test_shm_publisher_roundtrip() {
+        let iface = AppFixture::new("shm")
+            .interface_config(json!({"shm": {"pool_size": 65536, "min_payload_size": 16}}))
+            .publisher("OUT", "shm/roundtrip")
+            .subscriber("IN", "shm/roundtrip")
+            .zenoh_interface();
+        let session = iface.session().await.unwrap();
+        let provider = iface.get_shm_provider().unwrap();
+        assert!(provider.is_some());
+        let publisher = iface
+            .get_shm_publisher(session, "OUT", JsonEncoder::<Vec<u8>>::new(), provider)
+            .await
+            .unwrap();
+        let subscriber = iface.subscriber("IN").await.unwrap();
+
+        // One payload large enough for shared memory and one sent as a regular payload.
+        let large = vec![7u8; 1024];
+        publisher.publish(&large).await.unwrap();
+        publisher.publish(&vec![1u8]).await.unwrap();
+
+        let encoder = JsonEncoder::<Vec<u8>>::new();
+        for (expected, shared) in [(large, true), (vec![1u8], false)] {
+            let sample = tokio::time::timeout(Duration::from_secs(5), subscriber.recv_async())
+                .await
+                .expect("no sample received")
+                .unwrap();
+            assert_eq!(sample.payload().as_shm().is_some(), shared);
+            let received = encoder.decode(&sample.payload().to_bytes()).unwrap();
+            assert_eq!(received, expected);
+        }
+        iface.close().await.unwrap();
+    }
+}
@@ -0,0 +1,301 @@
+use crate::interfaces::zenoh::{
+    ConfiguredSubscriber, MessageMetadata, ZenohInterface, ZenohInterfaceError,
+};
+use futures::future::select_all;
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::Duration;
+use zenoh::sample::Sample;
+use zenoh::Session;
+
+/// Queue size used by a [`Synchronizer`] unless set with [`Synchronizer::with_queue_size`].
+pub const DEFAULT_SYNC_QUEUE_SIZE: usize = 10;
+
+/// How the samples of a [`Synchronizer`] tuple must line up in time.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SyncPolicy {
+    /// All samples carry the same timestamp.
+    Exact,
+    /// All samples lie within `slop` of each other.
+    Approximate { slop: Duration },
+}
+
+impl SyncPolicy {
+    fn slop_ns(&self) -> u64 {
+        match self {
+            SyncPolicy::Exact => 0,
+            SyncPolicy::Approximate { slop } => slop.as_nanos() as u64,
+        }
+    }
+}
+
+/// Reads the timestamp of a sample, in nanoseconds since the Unix epoch.
+pub type StampFn = Arc<dyn Fn(&Sample) -> Option<u64> + Send + Sync>;
+
+/// Where a [`Synchronizer`] reads the timestamp of a sample from, in nanoseconds since the
+/// Unix epoch.
+#[derive(Clone)]
+pub enum TimeSource {
+    /// The zenoh sample timestamp, or the publish time in the make87 metadata for samples
+    /// sent without one.
+    Sample,
+    /// The `timestamp` of the make87 message `Header`, which make87 messages carry as
+    /// their first field.
+    #[cfg(all(feature = "protobuf", feature = "make87_messages"))]
+    Header,
+    Custom(StampFn),
+}
+
+impl TimeSource {
+    pub fn stamp(&self, sample: &Sample) -> Option<u64> {
+        match self {
+            TimeSource::Sample => sample
+                .timestamp()
+                .map(|ts| ts.get_time().as_nanos())
+                .or_else(|| MessageMetadata::from_sample(sample).map(|m| m.timestamp_ns)),
+            #[cfg(all(feature = "protobuf", feature = "make87_messages"))]
+            TimeSource::Header => header_stamp(&sample.payload().to_bytes()),
+            TimeSource::Custom(stamp) => stamp(sample),
+        }
+    }
+}
+
+#[cfg(all(feature = "protobuf", feature = "make87_messages"))]
+#[derive(Clone, PartialEq, prost::Message)]
+struct HeaderOnly {
+    #[prost(message, optional, tag = "1")]
+    header: Option<make87_messages::core::Header>,
+}
+
+#[cfg(all(feature = "protobuf", feature = "make87_messages"))]
+fn header_stamp(payload: &[u8]) -> Option<u64> {
+    let timestamp = <HeaderOnly as prost::Message>::decode(payload)
+        .ok()?
+        .header?
+        .timestamp?;
+    let nanos = timestamp
+        .seconds
+        .checked_mul(1_000_000_000)?
+        .checked_add(i64::from(timestamp.nanos))?;
+    u64::try_from(nanos).ok()
+}
+
+/// Per-topic queues of stamped items, matched into tuples by a [`SyncPolicy`].
+struct SyncQueues<T> {
+    queues: Vec<VecDeque<(u64, T)>>,
+    queue_size: usize,
+    slop_ns: u64,
+}
+
+impl<T> SyncQueues<T> {
+    fn new(topics: usize, queue_size: usize, policy: SyncPolicy) -> Self {
+        Self {
+            queues: (0..topics).map(|_| VecDeque::new()).collect(),
+            queue_size: queue_size.max(1),
+            slop_ns: policy.slop_ns(),
+        }
+    }
+
+    /// Queue `item` of topic `index` and return a complete tuple if it completes one.
+    ///
+    /// The new item is matched with the closest item of every other topic. Once a tuple
+    /// is emitted, it and everything older are dropped from the queues.
+    fn push(&mut self, index: usize, stamp: u64, item: T) -> Option<Vec<(u64, T)>> {
+        let queue = &mut self.queues[index];
+        if queue.len() >= self.queue_size {
+            queue.pop_front();
+        }
+        queue.push_back((stamp, item));
+        let pivot = queue.len() - 1;
+
+        let mut picks = Vec::with_capacity(self.queues.len());
+        for (i, queue) in self.queues.iter().enumerate() {
+            let pick = if i == index {
+                pivot
+            } else {
+                queue
+                    .iter()
+                    .enumerate()
+                    .min_by_key(|(_, (ts, _))| ts.abs_diff(stamp))?
+                    .0
+            };
+            picks.push(pick);
+        }
+        let stamps = picks
+            .iter()
+            .zip(&self.queues)
+            .map(|(&pick, queue)| queue[pick].0);
+        let (min, max) = stamps.fold((u64::MAX, 0), |(lo, hi), ts| (lo.min(ts), hi.max(ts)));
+        if max - min > self.slop_ns {
+            return None;
+        }
+
+        let tuple = picks
+            .into_iter()
+            .zip(&mut self.queues)
+            .map(|(pick, queue)| {
+                queue.drain(..pick);
+                queue.pop_front().expect("picked item is queued")
+            })
+            .collect();
+        Some(tuple)
+    }
+}
+
+/// Samples matched across the topics of a [`Synchronizer`], in the order the topics were
+/// given.
+#[derive(Debug)]
+pub struct SyncedSamples {
+    pub samples: Vec<Sample>,
+    /// Timestamp of each sample, in nanoseconds since the Unix epoch.
+    pub stamps_ns: Vec<u64>,
+}
+
+/// Matches the samples of several configured subscribers into tuples by timestamp.
+pub struct Synchronizer {
+    subscribers: Vec<ConfiguredSubscriber>,
+    queues: SyncQueues<Sample>,
+    time_source: TimeSource,
+    unstamped: u64,
+}
+
+impl Synchronizer {
+    pub fn new(subscribers: Vec<ConfiguredSubscriber>, policy: SyncPolicy) -> Self {
+        let queues = SyncQueues::new(subscribers.len(), DEFAULT_SYNC_QUEUE_SIZE, policy);
+        Self {
+            subscribers,
+            queues,
+            time_source: TimeSource::Sample,
+            unstamped: 0,
+        }
+    }
+
+    /// Keep at most `queue_size` unmatched samples per topic, dropping the oldest.
+    pub fn with_queue_size(mut self, queue_size: usize) -> Self {
+        self.queues.queue_size = queue_size.max(1);
+        self
+    }
+
+    pub fn with_time_source(mut self, time_source: TimeSource) -> Self {
+        self.time_source = time_source;
+        self
+    }
+
+    /// Wait for the next matched tuple.
+    ///
+    /// Samples the time source finds no timestamp for are skipped and counted in
+    /// [`Synchronizer::unstamped`].
+    pub async fn recv(&mut self) -> Result<SyncedSamples, ZenohInterfaceError> {
+        if self.subscribers.is_empty() {
+            // Nothing to match, so no tuple ever completes.
+            return futures::future::pending().await;
+        }
+        loop {
+            let (sample, index) = {
+                let receives = self.subscribers.iter().map(|s| Box::pin(s.recv_async()));
+                let (sample, index, _) = select_all(receives).await;
+                (sample?, index)
+            };
+            let Some(stamp) = self.time_source.stamp(&sample) else {
+                self.unstamped += 1;
+                continue;
+            };
+            if let Some(tuple) = self.queues.push(index, stamp, sample) {
+                let (stamps_ns, samples) = tuple.into_iter().unzip();
+                return Ok(SyncedSamples { samples, stamps_ns });
+            }
+        }
+    }
+
+    /// Number of samples skipped for lack of a timestamp.
+    pub fn unstamped(&self) -> u64 {
+        self.unstamped
+    }
+
+    pub fn subscribers(&self) -> &[ConfiguredSubscriber] {
+        &self.subscribers
+    }
+}
+
+impl ZenohInterface {
+    /// Declare the subscribers named in `names` and synchronize them with `policy`.
+    pub async fn get_synchronizer(
+        &self,
+        session: &Session,
+        names: &[&str],
+        policy: SyncPolicy,
+    ) -> Result<Synchronizer, ZenohInterfaceError> {
+        let mut subscribers = Vec::with_capacity(names.len());
+        for name in names {
+            subscribers.push(self.get_subscriber(session, name).await?);
+        }
+        Ok(Synchronizer::new(subscribers, policy))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MS: u64 = 1_000_000;
+
+    #[test]
+    fn test_exact_policy() {
+        let mut queues = SyncQueues::new(2, 10, SyncPolicy::Exact);
+        assert!(queues.push(0, 10 * MS, "a10").is_none());
+        assert!(queues.push(0, 20 * MS, "a20").is_none());
+        assert!(queues.push(1, 15 * MS, "b15").is_none());
+        let tuple = queues.push(1, 20 * MS, "b20").unwrap();
+        assert_eq!(tuple, vec![(20 * MS, "a20"), (20 * MS, "b20")]);
+        // Older unmatched items were dropped along with the tuple.
+        assert!(queues.queues.iter().all(VecDeque::is_empty));
+    }
+
+    #[test]
+    fn test_approximate_policy() {
+        let slop = Duration::from_millis(5);
+        let mut queues = SyncQueues::new(3, 10, SyncPolicy::Approximate { slop });
+        for (i, ts) in [0, 10, 20, 30].into_iter().enumerate() {
+            assert!(queues.push(0, ts * MS, ("imu", i)).is_none());
+        }
+        assert!(queues.push(1, 19 * MS, ("depth", 0)).is_none());
+        let tuple = queues.push(2, 22 * MS, ("camera", 0)).unwrap();
+        let stamps: Vec<_> = tuple.iter().map(|(ts, _)| ts / MS).collect();
+        assert_eq!(stamps, vec![20, 19, 22]);
+        assert_eq!(queues.queues[0].len(), 1);
+    }
+
+    #[test]
+    fn test_approximate_policy_outside_slop() {
+        let slop = Duration::from_millis(5);
+        let mut queues = SyncQueues::new(2, 2, SyncPolicy::Approximate { slop });
+        assert!(queues.push(0, 0, "a0").is_none());
+        assert!(queues.push(1, 10 * MS, "b10").is_none());
+        assert!(queues.push(0, 20 * MS, "a20").is_none());
+        // The queue size of 2 drops a0 once a third item arrives.
+        assert!(queues.push(0, 30 * MS, "a30").is_none());
+        assert_eq!(queues.queues[0].front(), Some(&(20 * MS, "a20")));
+    }
+
+    #[cfg(all(feature = "protobuf", feature = "make87_messages"))]
+    #[test]
+    fn test_header_stamp() {
+        use make87_messages::core::Header;
+        use make87_messages::google::protobuf::Timestamp;
+        use make87_messages::text::PlainText;
+        use prost::Message;
+
+        let message = PlainText {
+            header: Some(Header {
+                timestamp: Some(Timestamp {
+                    seconds: 2,
+                    nanos: 500,
+                }),
+                ..Default::default()
+            }),
+            body: "hello".into(),
+        };
+        assert_eq!(header_stamp(&message.encode_to_vec()), Some(2_000_000_500));
+        assert_eq!(header_stamp(b"\xff"), None);
+    }
+}
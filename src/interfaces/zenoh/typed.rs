@@ -0,0 +1,237 @@
+use crate::encodings::Encoder;
+use crate::interfaces::zenoh::chunk;
+use crate::interfaces::zenoh::matching::MatchingBuffer;
+use crate::interfaces::zenoh::{
+    Chunker, ConfiguredSubscriber, GapDetector, MatchingExt, MatchingStream, MessageMetadata,
+    MetadataStamper, PublicationCache, ZenohInterfaceError,
+};
+use std::marker::PhantomData;
+use std::sync::Arc;
+use std::time::Duration;
+use zenoh::bytes::ZBytes;
+use zenoh::pubsub::Publisher;
+use zenoh::sample::Sample;
+
+/// A zenoh publisher bound to an [`Encoder`], publishing values of type `T`.
+pub struct TypedPublisher<T, E: Encoder<T>> {
+    publisher: Arc<Publisher<'static>>,
+    encoder: E,
+    metadata: Option<MetadataStamper>,
+    buffer: Option<MatchingBuffer>,
+    history: Option<PublicationCache>,
+    chunker: Option<Chunker>,
+    _marker: PhantomData<T>,
+}
+
+impl<T, E: Encoder<T>> TypedPublisher<T, E> {
+    pub fn new(publisher: Publisher<'static>, encoder: E) -> Self {
+        Self {
+            publisher: Arc::new(publisher),
+            encoder,
+            metadata: None,
+            buffer: None,
+            history: None,
+            chunker: None,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Attach metadata from `stamper` to every published sample.
+    pub fn with_metadata(mut self, stamper: MetadataStamper) -> Self {
+        self.metadata = Some(stamper);
+        self
+    }
+
+    /// Split encoded payloads larger than `chunk_size` bytes into chunks, which configured
+    /// subscribers reassemble. A `chunk_size` of `0` leaves chunking off.
+    ///
+    /// Set before [`TypedPublisher::buffer_until_matched`] for buffered samples to be
+    /// chunked as well.
+    pub fn with_chunking(mut self, chunk_size: usize) -> Self {
+        self.chunker = (chunk_size > 0).then(|| Chunker::new(chunk_size));
+        if let Some(history) = &self.history {
+            history.chunk_replies(self.chunker.clone(), self.publisher.encoding());
+        }
+        self
+    }
+
+    /// Hold up to `capacity` samples published while no subscriber matches, and send
+    /// them once one does. A `capacity` of `0` leaves buffering off.
+    pub async fn buffer_until_matched(
+        mut self,
+        capacity: usize,
+    ) -> Result<Self, ZenohInterfaceError> {
+        self.buffer = match capacity {
+            0 => None,
+            _ => {
+                let publisher = self.publisher.clone();
+                Some(MatchingBuffer::new(publisher, capacity, self.chunker.clone()).await?)
+            }
+        };
+        Ok(self)
+    }
+
+    /// Keep published samples in `cache` for subscribers that request history. History
+    /// is chunked like live samples.
+    pub fn with_history(mut self, cache: PublicationCache) -> Self {
+        cache.chunk_replies(self.chunker.clone(), self.publisher.encoding());
+        self.history = Some(cache);
+        self
+    }
+
+    /// Encode `value` and put it on the publisher's key expression.
+    ///
+    /// With [`TypedPublisher::buffer_until_matched`], the sample is held back instead while
+    /// no subscriber matches.
+    pub async fn publish(&self, value: &T) -> Result<(), ZenohInterfaceError> {
+        let payload = self
+            .encoder
+            .encode(value)
+            .map_err(ZenohInterfaceError::Encode)?;
+        let attachment = match &self.metadata {
+            Some(stamper) => Some(stamper.next().to_attachment()?),
+            None => None,
+        };
+        let payload: ZBytes = payload.into();
+        if let Some(history) = &self.history {
+            history.push(payload.clone(), attachment.clone());
+        }
+        match &self.buffer {
+            Some(buffer) => buffer.put(&self.publisher, payload, attachment).await?,
+            None => {
+                let chunker = self.chunker.as_ref();
+                chunk::put(&self.publisher, chunker, payload, attachment).await?
+            }
+        }
+        Ok(())
+    }
+
+    /// Number of samples held back until a subscriber matches.
+    pub async fn pending(&self) -> usize {
+        match &self.buffer {
+            Some(buffer) => buffer.pending().await,
+            None => 0,
+        }
+    }
+
+    pub async fn is_matching(&self) -> Result<bool, ZenohInterfaceError> {
+        self.publisher.is_matching().await
+    }
+
+    /// Wait until a subscriber matches, returning `false` if none did within `timeout`.
+    pub async fn wait_for_matching(&self, timeout: Duration) -> Result<bool, ZenohInterfaceError> {
+        self.publisher.wait_for_matching(timeout).await
+    }
+
+    pub async fn matching_changed(&self) -> Result<MatchingStream, ZenohInterfaceError> {
+        self.publisher.matching_changed().await
+    }
+
+    pub fn history(&self) -> Option<&PublicationCache> {
+        self.history.as_ref()
+    }
+
+    pub fn publisher(&self) -> &Publisher<'static> {
+        &self.publisher
+    }
+
+    pub fn encoder(&self) -> &E {
+        &self.encoder
+    }
+}
+
+/// A decoded sample together with the metadata its publisher attached.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TypedSample<T> {
+    pub value: T,
+    /// `None` for samples from publishers that do not attach make87 metadata.
+    pub metadata: Option<MessageMetadata>,
+    /// Samples of the same publisher lost since the previous one received.
+    pub missed: u64,
+}
+
+/// A configured zenoh subscriber bound to an [`Encoder`], receiving values of type `T`.
+pub struct TypedSubscriber<T, E: Encoder<T>> {
+    subscriber: ConfiguredSubscriber,
+    encoder: E,
+    message_type: Option<String>,
+    gaps: GapDetector,
+    _marker: PhantomData<T>,
+}
+
+impl<T, E: Encoder<T>> TypedSubscriber<T, E> {
+    pub fn new(subscriber: ConfiguredSubscriber, encoder: E) -> Self {
+        Self {
+            subscriber,
+            encoder,
+            message_type: None,
+            gaps: GapDetector::default(),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Reject samples whose metadata names a message type other than `message_type`.
+    pub fn with_message_type(mut self, message_type: &str) -> Self {
+        self.message_type = Some(message_type.to_string());
+        self
+    }
+
+    /// Wait for the next sample and decode it.
+    ///
+    /// A payload that cannot be decoded is returned as [`ZenohInterfaceError::Decode`];
+    /// the subscriber stays usable and the next call receives the following sample.
+    pub async fn recv(&self) -> Result<T, ZenohInterfaceError> {
+        Ok(self.recv_sample().await?.value)
+    }
+
+    /// Wait for the next sample and decode it along with its metadata.
+    ///
+    /// Samples of an unexpected message type are returned as
+    /// [`ZenohInterfaceError::UnexpectedMessageType`] without being decoded.
+    pub async fn recv_sample(&self) -> Result<TypedSample<T>, ZenohInterfaceError> {
+        let sample = self.subscriber.recv_async().await?;
+        self.decode_sample(&sample)
+    }
+
+    /// Decode a sample received through [`TypedSubscriber::subscriber`].
+    pub fn decode_sample(&self, sample: &Sample) -> Result<TypedSample<T>, ZenohInterfaceError> {
+        let metadata = MessageMetadata::from_sample(sample);
+        let missed = metadata
+            .as_ref()
+            .map(|metadata| self.gaps.observe(metadata))
+            .unwrap_or_default();
+        if let (Some(expected), Some(metadata)) = (&self.message_type, &metadata) {
+            if metadata.message_type != *expected {
+                return Err(ZenohInterfaceError::UnexpectedMessageType {
+                    expected: expected.clone(),
+                    received: metadata.message_type.clone(),
+                });
+            }
+        }
+        let value = self
+            .encoder
+            .decode(&sample.payload().to_bytes())
+            .map_err(|e| {
+                self.subscriber.record_decode_failure();
+                ZenohInterfaceError::Decode(e)
+            })?;
+        Ok(TypedSample {
+            value,
+            metadata,
+            missed,
+        })
+    }
+
+    /// Total number of samples lost across all publishers on this topic.
+    pub fn missed_samples(&self) -> u64 {
+        self.gaps.missed_total()
+    }
+
+    pub fn subscriber(&self) -> &ConfiguredSubscriber {
+        &self.subscriber
+    }
+
+    pub fn encoder(&self) -> &E {
+        &self.encoder
+    }
+}
@@ -0,0 +1,261 @@
+use crate::interfaces::zenoh::{
+    ZenohInterface, ZenohInterfaceConfig, ZenohInterfaceError, ZenohPublisherConfig,
+    ZenohQuerierConfig, ZenohQueryableConfig, ZenohSubscriberConfig,
+};
+use crate::models::InterfaceConfig;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+/// Problems found in the zenoh settings of one config section.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ConfigIssue {
+    /// `interface`, `publisher`, `subscriber`, `requester` or `provider`.
+    pub section: &'static str,
+    /// Interface, topic or endpoint name.
+    pub name: String,
+    /// Keys that none of the zenoh settings for this section recognize.
+    pub unknown_keys: Vec<String>,
+    /// Why the recognized keys could not be decoded, if they could not.
+    pub error: Option<String>,
+}
+
+impl fmt::Display for ConfigIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} '{}':", self.section, self.name)?;
+        if !self.unknown_keys.is_empty() {
+            write!(f, " unknown keys [{}]", self.unknown_keys.join(", "))?;
+        }
+        if let Some(error) = &self.error {
+            write!(f, " invalid value: {}", error)?;
+        }
+        Ok(())
+    }
+}
+
+/// Result of [`validate_interface_config`], listing every section with problems.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ValidationReport {
+    pub issues: Vec<ConfigIssue>,
+}
+
+impl ValidationReport {
+    pub fn is_ok(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+impl fmt::Display for ValidationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, issue) in self.issues.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{}", issue)?;
+        }
+        Ok(())
+    }
+}
+
+/// Check the zenoh settings of every topic and endpoint of `config` against the zenoh
+/// config models, without opening a session.
+pub fn validate_interface_config(config: &InterfaceConfig) -> ValidationReport {
+    let mut issues = Vec::new();
+    let mut check = |section, name: &str, issue: Option<(Vec<String>, Option<String>)>| {
+        if let Some((unknown_keys, error)) = issue {
+            issues.push(ConfigIssue {
+                section,
+                name: name.to_string(),
+                unknown_keys,
+                error,
+            });
+        }
+    };
+
+    check(
+        "interface",
+        &config.name,
+        check_section::<ZenohInterfaceConfig>(&config.config),
+    );
+    for (name, publisher) in &config.publishers {
+        check(
+            "publisher",
+            name,
+            check_section::<ZenohPublisherConfig>(&publisher.config),
+        );
+    }
+    for (name, subscriber) in &config.subscribers {
+        check(
+            "subscriber",
+            name,
+            check_section::<ZenohSubscriberConfig>(&subscriber.config.config),
+        );
+    }
+    for (name, requester) in &config.requesters {
+        check(
+            "requester",
+            name,
+            check_section::<ZenohQuerierConfig>(&requester.config.config),
+        );
+    }
+    for (name, provider) in &config.providers {
+        check(
+            "provider",
+            name,
+            check_section::<ZenohQueryableConfig>(&provider.config),
+        );
+    }
+    ValidationReport { issues }
+}
+
+/// Unknown keys and decode error of `map` as a `T`, or `None` if it is valid.
+fn check_section<T>(map: &BTreeMap<String, Value>) -> Option<(Vec<String>, Option<String>)>
+where
+    T: Serialize + DeserializeOwned + Default,
+{
+    let known: BTreeSet<String> = match serde_json::to_value(T::default()) {
+        Ok(Value::Object(fields)) => fields.into_iter().map(|(key, _)| key).collect(),
+        _ => BTreeSet::new(),
+    };
+    let unknown_keys: Vec<String> = map
+        .keys()
+        .filter(|key| !known.contains(*key))
+        .cloned()
+        .collect();
+    let error = serde_json::from_value::<T>(Value::Object(map.clone().into_iter().collect()))
+        .err()
+        .map(|e| e.to_string());
+    if unknown_keys.is_empty() && error.is_none() {
+        None
+    } else {
+        Some((unknown_keys, error))
+    }
+}
+
+impl ZenohInterface {
+    /// Validate this interface's zenoh settings, see [`validate_interface_config`].
+    ///
+    /// An interface missing from the application config has nothing to validate.
+    pub fn validation_report(&self) -> ValidationReport {
+        self.interface_config()
+            .map(validate_interface_config)
+            .unwrap_or_default()
+    }
+
+    /// Fail with [`ZenohInterfaceError::InvalidConfig`] if [`Self::validation_report`]
+    /// found any issue.
+    pub fn validate(&self) -> Result<(), ZenohInterfaceError> {
+        let report = self.validation_report();
+        if report.is_ok() {
+            Ok(())
+        } else {
+            Err(ZenohInterfaceError::InvalidConfig(report))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{ProviderEndpointConfig, PublisherTopicConfig};
+    use serde_json::json;
+
+    fn interface_config() -> InterfaceConfig {
+        InterfaceConfig {
+            name: "zenoh".to_string(),
+            publishers: BTreeMap::new(),
+            subscribers: BTreeMap::new(),
+            requesters: BTreeMap::new(),
+            providers: BTreeMap::new(),
+            clients: BTreeMap::new(),
+            servers: BTreeMap::new(),
+            config: BTreeMap::new(),
+        }
+    }
+
+    fn publisher(config: Value) -> PublisherTopicConfig {
+        PublisherTopicConfig {
+            topic_name: "CAMERA".into(),
+            topic_key: "camera".into(),
+            message_type: "ImageJPEG".into(),
+            interface_name: "zenoh".into(),
+            config: serde_json::from_value(config).unwrap(),
+            protocol: "zenoh".into(),
+            encoding: None,
+        }
+    }
+
+    fn provider(config: Value) -> ProviderEndpointConfig {
+        ProviderEndpointConfig {
+            endpoint_name: "RESIZE".into(),
+            endpoint_key: "resize".into(),
+            requester_message_type: "ReqType".into(),
+            provider_message_type: "PrvType".into(),
+            interface_name: "zenoh".into(),
+            config: serde_json::from_value(config).unwrap(),
+            protocol: "zenoh".into(),
+            encoding: None,
+        }
+    }
+
+    #[test]
+    fn test_valid_config() {
+        let mut config = interface_config();
+        config
+            .publishers
+            .insert("CAMERA".into(), publisher(json!({"priority": "REAL_TIME"})));
+        config.providers.insert(
+            "RESIZE".into(),
+            provider(json!({"handler": {"handler_type": "RING", "capacity": 1}})),
+        );
+        config.config = serde_json::from_value(json!({"listen_port": 7500})).unwrap();
+        assert!(validate_interface_config(&config).is_ok());
+    }
+
+    #[test]
+    fn test_reports_unknown_and_invalid_keys() {
+        let mut config = interface_config();
+        config.publishers.insert(
+            "CAMERA".into(),
+            publisher(json!({"expres": true, "priority": "URGENT"})),
+        );
+        config.providers.insert(
+            "RESIZE".into(),
+            provider(json!({"handler": {"capacity": 1}})),
+        );
+        config.config = serde_json::from_value(json!({"listen_prot": 7500})).unwrap();
+
+        let report = validate_interface_config(&config);
+        assert_eq!(report.issues.len(), 3);
+
+        let interface = &report.issues[0];
+        assert_eq!(
+            (interface.section, interface.name.as_str()),
+            ("interface", "zenoh")
+        );
+        assert_eq!(interface.unknown_keys, vec!["listen_prot"]);
+        assert!(interface.error.is_none());
+
+        let publisher = &report.issues[1];
+        assert_eq!(
+            (publisher.section, publisher.name.as_str()),
+            ("publisher", "CAMERA")
+        );
+        assert_eq!(publisher.unknown_keys, vec!["expres"]);
+        assert!(publisher.error.as_ref().unwrap().contains("URGENT"));
+
+        let provider = &report.issues[2];
+        assert_eq!(
+            (provider.section, provider.name.as_str()),
+            ("provider", "RESIZE")
+        );
+        assert!(provider.unknown_keys.is_empty());
+        assert!(provider.error.as_ref().unwrap().contains("handler_type"));
+
+        assert!(report
+            .to_string()
+            .starts_with("interface 'zenoh': unknown keys [listen_prot]\npublisher 'CAMERA':"));
+    }
+}
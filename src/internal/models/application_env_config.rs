@@ -46,6 +46,28 @@ pub struct InterfaceConfig {
     pub providers: BTreeMap<String, ProviderEndpointConfig>,
     pub clients: BTreeMap<String, BoundClient>,
     pub servers: BTreeMap<String, ServerServiceConfig>,
+    /// Interface-level settings, i.e. every key besides the entity maps above.
+    #[serde(flatten)]
+    pub config: BTreeMap<String, serde_json::Value>,
+}
+
+impl InterfaceConfig {
+    /// An interface called `name` without any entities or settings.
+    ///
+    /// Struct literals can fill the fields they do not set from it, and so keep compiling
+    /// when fields are added: `InterfaceConfig { publishers, ..InterfaceConfig::new("zenoh") }`.
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            publishers: BTreeMap::new(),
+            subscribers: BTreeMap::new(),
+            requesters: BTreeMap::new(),
+            providers: BTreeMap::new(),
+            clients: BTreeMap::new(),
+            servers: BTreeMap::new(),
+            config: BTreeMap::new(),
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone)]
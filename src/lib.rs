@@ -5,9 +5,14 @@ pub mod config;
 mod internal;
 pub mod models;
 pub mod peripherals;
+pub mod runtime;
+pub mod secrets;
 #[cfg(feature = "storage")]
 pub mod storage;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
 
+#[deprecated(note = "use `runtime::Runtime::run_forever`, which shuts down cleanly on signals")]
 pub fn run_forever() {
     loop {
         std::thread::park();
@@ -0,0 +1,537 @@
+#[cfg(feature = "zenoh")]
+use crate::interfaces::zenoh::{
+    ZenohInterfaceConfig, ZenohPublisherConfig, ZenohQuerierConfig, ZenohQueryableConfig,
+    ZenohSubscriberConfig,
+};
+use crate::models::{
+    AccessPoint, ApplicationConfig, ApplicationInfo, BoundRequester, BoundSubscriber,
+    InterfaceConfig, MountedPeripherals, ProviderEndpointConfig, PublisherTopicConfig,
+    RequesterEndpointConfig, StorageConfig, SubscriberTopicConfig,
+};
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+
+/// Serialize `value` into config keys, leaving out unset optional keys.
+///
+/// # Panics
+///
+/// If `value` does not serialize to a JSON object.
+#[cfg(feature = "zenoh")]
+fn to_config_map(value: impl Serialize) -> BTreeMap<String, Value> {
+    match serde_json::to_value(value) {
+        Ok(Value::Object(fields)) => fields.into_iter().filter(|(_, v)| !v.is_null()).collect(),
+        Ok(other) => panic!("Config must serialize to a JSON object, got {other}"),
+        Err(e) => panic!("Config is not serializable: {e}"),
+    }
+}
+
+fn to_value(value: impl Serialize) -> Value {
+    serde_json::to_value(value).unwrap_or_else(|e| panic!("Config is not serializable: {e}"))
+}
+
+impl ApplicationConfig {
+    /// Build a config in code rather than from `MAKE87_CONFIG`, e.g. for tests and local
+    /// runs.
+    ///
+    /// ```ignore
+    /// let config = ApplicationConfig::builder()
+    ///     .app("detector")
+    ///     .zenoh("zenoh", |i| {
+    ///         i.publisher("DETECTIONS", "detector/detections")
+    ///             .qos(ZenohPublisherConfig {
+    ///                 priority: Priority::RealTime,
+    ///                 ..Default::default()
+    ///             })
+    ///             .subscriber("IMAGE", "camera/image")
+    ///     })
+    ///     .build();
+    /// ```
+    pub fn builder() -> ApplicationConfigBuilder {
+        ApplicationConfigBuilder::new()
+    }
+}
+
+/// Builds an [`ApplicationConfig`], see [`ApplicationConfig::builder`].
+#[derive(Clone)]
+pub struct ApplicationConfigBuilder {
+    config: ApplicationConfig,
+}
+
+impl Default for ApplicationConfigBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Continue building on an existing config, e.g. one loaded from `MAKE87_CONFIG`.
+impl From<ApplicationConfig> for ApplicationConfigBuilder {
+    fn from(config: ApplicationConfig) -> Self {
+        Self { config }
+    }
+}
+
+impl ApplicationConfigBuilder {
+    pub fn new() -> Self {
+        Self {
+            config: ApplicationConfig {
+                interfaces: BTreeMap::new(),
+                peripherals: MountedPeripherals {
+                    peripherals: vec![],
+                },
+                config: json!({}),
+                storage: None,
+                application_info: ApplicationInfo {
+                    deployed_application_id: String::new(),
+                    deployed_application_name: String::new(),
+                    system_id: String::new(),
+                    application_id: String::new(),
+                    application_name: String::new(),
+                    git_url: None,
+                    git_branch: None,
+                    is_release_version: false,
+                },
+            },
+        }
+    }
+
+    /// Name the application and its deployment `name`.
+    pub fn app(mut self, name: &str) -> Self {
+        let info = &mut self.config.application_info;
+        info.application_id = name.to_string();
+        info.application_name = name.to_string();
+        info.deployed_application_id = name.to_string();
+        info.deployed_application_name = name.to_string();
+        self
+    }
+
+    pub fn system_id(mut self, system_id: &str) -> Self {
+        self.config.application_info.system_id = system_id.to_string();
+        self
+    }
+
+    pub fn application_info(mut self, info: ApplicationInfo) -> Self {
+        self.config.application_info = info;
+        self
+    }
+
+    /// The application's own config, as read from `config`.
+    pub fn config(mut self, config: impl Serialize) -> Self {
+        self.config.config = to_value(config);
+        self
+    }
+
+    pub fn storage(mut self, storage: StorageConfig) -> Self {
+        self.config.storage = Some(storage);
+        self
+    }
+
+    /// Add the zenoh interface `name`, or extend it if it was already added.
+    pub fn zenoh<F, R>(mut self, name: &str, build: F) -> Self
+    where
+        F: FnOnce(InterfaceBuilder) -> R,
+        R: Into<InterfaceBuilder>,
+    {
+        let interface = self
+            .config
+            .interfaces
+            .remove(name)
+            .unwrap_or_else(|| InterfaceConfig::new(name));
+        let interface = build(InterfaceBuilder { interface }).into().interface;
+        self.config.interfaces.insert(name.to_string(), interface);
+        self
+    }
+
+    pub fn build(self) -> ApplicationConfig {
+        self.config
+    }
+}
+
+/// Adds topics and endpoints to one interface, see [`ApplicationConfigBuilder::zenoh`].
+pub struct InterfaceBuilder {
+    interface: InterfaceConfig,
+}
+
+impl InterfaceBuilder {
+    /// Set the interface config key `key`.
+    pub fn set(mut self, key: &str, value: impl Serialize) -> Self {
+        self.interface
+            .config
+            .insert(key.to_string(), to_value(value));
+        self
+    }
+
+    /// Set every interface-level zenoh setting from `settings`.
+    #[cfg(feature = "zenoh")]
+    pub fn settings(mut self, settings: ZenohInterfaceConfig) -> Self {
+        self.interface.config.extend(to_config_map(settings));
+        self
+    }
+
+    pub fn publisher(self, name: &str, topic_key: &str) -> EntityBuilder<PublisherTopicConfig> {
+        let entity = PublisherTopicConfig {
+            topic_name: name.to_string(),
+            topic_key: topic_key.to_string(),
+            message_type: String::new(),
+            interface_name: self.interface.name.clone(),
+            config: BTreeMap::new(),
+            protocol: "zenoh".to_string(),
+            encoding: Some("proto".to_string()),
+        };
+        EntityBuilder::new(self, name, entity)
+    }
+
+    /// Add a subscriber bound to the publisher at `127.0.0.1:7447` on the same node, until
+    /// [`EntityBuilder::access_point`] sets another.
+    pub fn subscriber(self, name: &str, topic_key: &str) -> EntityBuilder<BoundSubscriber> {
+        let entity = BoundSubscriber {
+            access_point: local_access_point(),
+            config: SubscriberTopicConfig {
+                topic_name: name.to_string(),
+                topic_key: topic_key.to_string(),
+                message_type: String::new(),
+                interface_name: self.interface.name.clone(),
+                config: BTreeMap::new(),
+                protocol: "zenoh".to_string(),
+                encoding: Some("proto".to_string()),
+            },
+        };
+        EntityBuilder::new(self, name, entity)
+    }
+
+    /// Add a requester bound to the provider at `127.0.0.1:7447` on the same node, until
+    /// [`EntityBuilder::access_point`] sets another.
+    pub fn requester(self, name: &str, endpoint_key: &str) -> EntityBuilder<BoundRequester> {
+        let entity = BoundRequester {
+            access_point: local_access_point(),
+            config: RequesterEndpointConfig {
+                endpoint_name: name.to_string(),
+                endpoint_key: endpoint_key.to_string(),
+                requester_message_type: String::new(),
+                provider_message_type: String::new(),
+                interface_name: self.interface.name.clone(),
+                config: BTreeMap::new(),
+                protocol: "zenoh".to_string(),
+                encoding: Some("proto".to_string()),
+            },
+        };
+        EntityBuilder::new(self, name, entity)
+    }
+
+    pub fn provider(self, name: &str, endpoint_key: &str) -> EntityBuilder<ProviderEndpointConfig> {
+        let entity = ProviderEndpointConfig {
+            endpoint_name: name.to_string(),
+            endpoint_key: endpoint_key.to_string(),
+            requester_message_type: String::new(),
+            provider_message_type: String::new(),
+            interface_name: self.interface.name.clone(),
+            config: BTreeMap::new(),
+            protocol: "zenoh".to_string(),
+            encoding: Some("proto".to_string()),
+        };
+        EntityBuilder::new(self, name, entity)
+    }
+}
+
+/// The default zenoh listener of another application on this node.
+fn local_access_point() -> AccessPoint {
+    AccessPoint {
+        vpn_ip: "127.0.0.1".to_string(),
+        vpn_port: 7447,
+        public_ip: None,
+        public_port: None,
+        same_node: true,
+    }
+}
+
+/// A topic or endpoint entry of an [`InterfaceConfig`].
+pub trait InterfaceEntity: Sized {
+    fn config_mut(&mut self) -> &mut BTreeMap<String, Value>;
+    fn encoding_mut(&mut self) -> &mut Option<String>;
+    fn insert(self, name: String, interface: &mut InterfaceConfig);
+}
+
+impl InterfaceEntity for PublisherTopicConfig {
+    fn config_mut(&mut self) -> &mut BTreeMap<String, Value> {
+        &mut self.config
+    }
+
+    fn encoding_mut(&mut self) -> &mut Option<String> {
+        &mut self.encoding
+    }
+
+    fn insert(self, name: String, interface: &mut InterfaceConfig) {
+        interface.publishers.insert(name, self);
+    }
+}
+
+impl InterfaceEntity for BoundSubscriber {
+    fn config_mut(&mut self) -> &mut BTreeMap<String, Value> {
+        &mut self.config.config
+    }
+
+    fn encoding_mut(&mut self) -> &mut Option<String> {
+        &mut self.config.encoding
+    }
+
+    fn insert(self, name: String, interface: &mut InterfaceConfig) {
+        interface.subscribers.insert(name, self);
+    }
+}
+
+impl InterfaceEntity for BoundRequester {
+    fn config_mut(&mut self) -> &mut BTreeMap<String, Value> {
+        &mut self.config.config
+    }
+
+    fn encoding_mut(&mut self) -> &mut Option<String> {
+        &mut self.config.encoding
+    }
+
+    fn insert(self, name: String, interface: &mut InterfaceConfig) {
+        interface.requesters.insert(name, self);
+    }
+}
+
+impl InterfaceEntity for ProviderEndpointConfig {
+    fn config_mut(&mut self) -> &mut BTreeMap<String, Value> {
+        &mut self.config
+    }
+
+    fn encoding_mut(&mut self) -> &mut Option<String> {
+        &mut self.encoding
+    }
+
+    fn insert(self, name: String, interface: &mut InterfaceConfig) {
+        interface.providers.insert(name, self);
+    }
+}
+
+/// Configures the topic or endpoint just added to an interface.
+///
+/// Adding the next entity, or converting back into an [`InterfaceBuilder`], commits it.
+pub struct EntityBuilder<E: InterfaceEntity> {
+    interface: InterfaceBuilder,
+    name: String,
+    entity: E,
+}
+
+impl<E: InterfaceEntity> EntityBuilder<E> {
+    fn new(interface: InterfaceBuilder, name: &str, entity: E) -> Self {
+        Self {
+            interface,
+            name: name.to_string(),
+            entity,
+        }
+    }
+
+    /// Set the entity config key `key`.
+    pub fn set(mut self, key: &str, value: impl Serialize) -> Self {
+        self.entity
+            .config_mut()
+            .insert(key.to_string(), to_value(value));
+        self
+    }
+
+    /// Encoding of the payloads, `proto` unless set. `None` leaves it to the encoder.
+    pub fn encoding(mut self, encoding: Option<&str>) -> Self {
+        *self.entity.encoding_mut() = encoding.map(str::to_string);
+        self
+    }
+
+    /// Commit this entity and go back to the interface.
+    pub fn done(self) -> InterfaceBuilder {
+        let mut interface = self.interface;
+        self.entity.insert(self.name, &mut interface.interface);
+        interface
+    }
+
+    pub fn publisher(self, name: &str, topic_key: &str) -> EntityBuilder<PublisherTopicConfig> {
+        self.done().publisher(name, topic_key)
+    }
+
+    pub fn subscriber(self, name: &str, topic_key: &str) -> EntityBuilder<BoundSubscriber> {
+        self.done().subscriber(name, topic_key)
+    }
+
+    pub fn requester(self, name: &str, endpoint_key: &str) -> EntityBuilder<BoundRequester> {
+        self.done().requester(name, endpoint_key)
+    }
+
+    pub fn provider(self, name: &str, endpoint_key: &str) -> EntityBuilder<ProviderEndpointConfig> {
+        self.done().provider(name, endpoint_key)
+    }
+
+    #[cfg(feature = "zenoh")]
+    fn extend(mut self, settings: impl Serialize) -> Self {
+        self.entity.config_mut().extend(to_config_map(settings));
+        self
+    }
+}
+
+impl<E: InterfaceEntity> From<EntityBuilder<E>> for InterfaceBuilder {
+    fn from(builder: EntityBuilder<E>) -> Self {
+        builder.done()
+    }
+}
+
+impl EntityBuilder<PublisherTopicConfig> {
+    pub fn message_type(mut self, message_type: &str) -> Self {
+        self.entity.message_type = message_type.to_string();
+        self
+    }
+
+    /// Set every zenoh setting of the publisher from `qos`.
+    #[cfg(feature = "zenoh")]
+    pub fn qos(self, qos: ZenohPublisherConfig) -> Self {
+        self.extend(qos)
+    }
+}
+
+impl EntityBuilder<BoundSubscriber> {
+    pub fn message_type(mut self, message_type: &str) -> Self {
+        self.entity.config.message_type = message_type.to_string();
+        self
+    }
+
+    pub fn access_point(mut self, access_point: AccessPoint) -> Self {
+        self.entity.access_point = access_point;
+        self
+    }
+
+    /// Set every zenoh setting of the subscriber from `qos`.
+    #[cfg(feature = "zenoh")]
+    pub fn qos(self, qos: ZenohSubscriberConfig) -> Self {
+        self.extend(qos)
+    }
+}
+
+impl EntityBuilder<BoundRequester> {
+    pub fn message_types(mut self, requester: &str, provider: &str) -> Self {
+        self.entity.config.requester_message_type = requester.to_string();
+        self.entity.config.provider_message_type = provider.to_string();
+        self
+    }
+
+    pub fn access_point(mut self, access_point: AccessPoint) -> Self {
+        self.entity.access_point = access_point;
+        self
+    }
+
+    /// Set every zenoh setting of the requester from `qos`.
+    #[cfg(feature = "zenoh")]
+    pub fn qos(self, qos: ZenohQuerierConfig) -> Self {
+        self.extend(qos)
+    }
+}
+
+impl EntityBuilder<ProviderEndpointConfig> {
+    pub fn message_types(mut self, requester: &str, provider: &str) -> Self {
+        self.entity.requester_message_type = requester.to_string();
+        self.entity.provider_message_type = provider.to_string();
+        self
+    }
+
+    /// Set every zenoh setting of the provider from `qos`.
+    #[cfg(feature = "zenoh")]
+    pub fn qos(self, qos: ZenohQueryableConfig) -> Self {
+        self.extend(qos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::load_config_from_json;
+
+    #[test]
+    fn test_builder_roundtrips_through_json() {
+        let config = ApplicationConfig::builder()
+            .app("detector")
+            .system_id("system")
+            .config(json!({"threshold": 0.5}))
+            .zenoh("zenoh", |i| {
+                i.set("listen_port", 7500)
+                    .publisher("DETECTIONS", "detector/detections")
+                    .message_type("Detections")
+                    .set("express", true)
+                    .subscriber("IMAGE", "camera/image")
+                    .encoding(Some("json"))
+                    .requester("DESCRIBE", "describe")
+                    .message_types("Image", "Text")
+            })
+            .zenoh("zenoh", |i| i.provider("STATUS", "status"))
+            .build();
+
+        let config = load_config_from_json(serde_json::to_string(&config).unwrap()).unwrap();
+        assert_eq!(config.application_info.application_name, "detector");
+        assert_eq!(config.application_info.system_id, "system");
+        assert_eq!(config.config["threshold"], json!(0.5));
+        let iface = &config.interfaces["zenoh"];
+        assert_eq!(iface.config["listen_port"], json!(7500));
+        let publisher = &iface.publishers["DETECTIONS"];
+        assert_eq!(publisher.message_type, "Detections");
+        assert_eq!(publisher.interface_name, "zenoh");
+        assert_eq!(publisher.config["express"], json!(true));
+        let subscriber = &iface.subscribers["IMAGE"];
+        assert_eq!(subscriber.config.encoding.as_deref(), Some("json"));
+        assert_eq!(subscriber.access_point.vpn_port, 7447);
+        assert_eq!(
+            iface.requesters["DESCRIBE"].config.provider_message_type,
+            "Text"
+        );
+        assert!(iface.providers.contains_key("STATUS"));
+    }
+
+    #[cfg(feature = "zenoh")]
+    #[test]
+    fn test_typed_qos_is_valid() {
+        use crate::interfaces::zenoh::{
+            validate_interface_config, HandlerChannel, Priority, QueryTarget, Reliability,
+        };
+
+        let config = ApplicationConfig::builder()
+            .zenoh("zenoh", |i| {
+                i.settings(ZenohInterfaceConfig {
+                    listen_endpoints: Some(vec![]),
+                    ..Default::default()
+                })
+                .publisher("OUT", "out")
+                .qos(ZenohPublisherConfig {
+                    priority: Priority::RealTime,
+                    reliability: Reliability::BestEffort,
+                    ..Default::default()
+                })
+                .subscriber("IN", "in")
+                .qos(ZenohSubscriberConfig {
+                    handler: HandlerChannel::Ring { capacity: 1 },
+                    ..Default::default()
+                })
+                .requester("REQ", "req")
+                .qos(ZenohQuerierConfig {
+                    target: QueryTarget::All,
+                    ..Default::default()
+                })
+                .provider("PRV", "prv")
+                .qos(ZenohQueryableConfig::default())
+            })
+            .build();
+
+        let iface = &config.interfaces["zenoh"];
+        assert!(validate_interface_config(iface).is_ok());
+        assert_eq!(iface.config["listen_endpoints"], json!([]));
+        assert!(!iface.config.contains_key("shm"));
+        assert_eq!(
+            iface.publishers["OUT"].config["priority"],
+            json!("REAL_TIME")
+        );
+        assert_eq!(
+            iface.subscribers["IN"].config.config["handler"],
+            json!({"handler_type": "RING", "capacity": 1})
+        );
+        assert_eq!(
+            iface.requesters["REQ"].config.config["target"],
+            json!("ALL")
+        );
+    }
+}
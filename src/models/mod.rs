@@ -1,2 +1,5 @@
+mod builder;
+
 pub use crate::internal::models::application_env_config::ApplicationEnvConfig as ApplicationConfig;
-pub use crate::internal::models::application_env_config::*;
\ No newline at end of file
+pub use crate::internal::models::application_env_config::*;
+pub use builder::*;
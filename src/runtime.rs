@@ -0,0 +1,359 @@
+use std::error::Error;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tokio::time::{timeout_at, Instant};
+use tokio_util::sync::CancellationToken;
+use tokio_util::task::TaskTracker;
+
+/// Time tasks and shutdown hooks get to finish once shutdown starts, unless set with
+/// [`Runtime::with_grace_period`].
+pub const DEFAULT_GRACE_PERIOD: Duration = Duration::from_secs(5);
+
+#[derive(Debug, thiserror::Error)]
+pub enum RuntimeError {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error("Application task failed: {0}")]
+    Join(#[from] tokio::task::JoinError),
+    #[error("Application failed: {0}")]
+    Application(Box<dyn Error + Send + Sync>),
+    #[error("Shutdown did not finish within the grace period of {0:?}")]
+    GracePeriodExceeded(Duration),
+}
+
+type ShutdownHook = Box<dyn FnOnce() -> Pin<Box<dyn Future<Output = ()> + Send>> + Send>;
+
+/// Shared by all tasks of a [`Runtime`]: the cancellation token they watch, the tracker
+/// that lets shutdown wait for them, and the hooks run once they are done.
+#[derive(Clone)]
+pub struct RuntimeContext {
+    token: CancellationToken,
+    tracker: TaskTracker,
+    hooks: Arc<Mutex<Vec<ShutdownHook>>>,
+}
+
+impl RuntimeContext {
+    fn new() -> Self {
+        Self {
+            token: CancellationToken::new(),
+            tracker: TaskTracker::new(),
+            hooks: Arc::default(),
+        }
+    }
+
+    /// Token cancelled when shutdown starts.
+    pub fn token(&self) -> &CancellationToken {
+        &self.token
+    }
+
+    /// Wait until shutdown starts.
+    pub async fn cancelled(&self) {
+        self.token.cancelled().await
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.token.is_cancelled()
+    }
+
+    /// Start shutting down, as a signal would.
+    pub fn shutdown(&self) {
+        self.token.cancel()
+    }
+
+    /// Spawn a task that shutdown waits for, up to the grace period.
+    pub fn spawn<F>(&self, task: F) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        self.tracker.spawn(task)
+    }
+
+    /// Run `hook` once all tasks finished, in the order hooks were added.
+    pub fn on_shutdown<F, Fut>(&self, hook: F)
+    where
+        F: FnOnce() -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let mut hooks = self.hooks.lock().unwrap_or_else(|e| e.into_inner());
+        hooks.push(Box::new(move || Box::pin(hook())));
+    }
+
+    /// Close `session` on shutdown, undeclaring every publisher, subscriber, queryable and
+    /// liveliness token still declared on it.
+    #[cfg(feature = "zenoh")]
+    pub fn close_on_shutdown(&self, session: zenoh::Session) {
+        self.on_shutdown(move || async move {
+            if let Err(e) = session.close().await {
+                eprintln!("Failed to close zenoh session: {e}");
+            }
+        });
+    }
+
+    /// Flush `stream` on shutdown so that no logged data is lost.
+    #[cfg(feature = "rerun")]
+    pub fn flush_on_shutdown(&self, stream: rerun::RecordingStream) {
+        self.on_shutdown(move || async move {
+            let flushed = tokio::task::spawn_blocking(move || stream.flush_blocking());
+            if let Err(e) = flushed.await {
+                eprintln!("Failed to flush rerun stream: {e}");
+            }
+        });
+    }
+
+    fn take_hooks(&self) -> Vec<ShutdownHook> {
+        let mut hooks = self.hooks.lock().unwrap_or_else(|e| e.into_inner());
+        std::mem::take(&mut *hooks)
+    }
+}
+
+/// Owns the tokio runtime of an application and shuts it down cleanly on SIGINT or
+/// SIGTERM, or once the main task returns.
+///
+/// Shutdown cancels the [`RuntimeContext::token`], waits for the tasks spawned through the
+/// context and then runs the shutdown hooks, all within the grace period.
+pub struct Runtime {
+    runtime: tokio::runtime::Runtime,
+    context: RuntimeContext,
+    grace_period: Duration,
+}
+
+impl Runtime {
+    pub fn new() -> Result<Self, RuntimeError> {
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()?;
+        Ok(Self {
+            runtime,
+            context: RuntimeContext::new(),
+            grace_period: DEFAULT_GRACE_PERIOD,
+        })
+    }
+
+    pub fn with_grace_period(mut self, grace_period: Duration) -> Self {
+        self.grace_period = grace_period;
+        self
+    }
+
+    pub fn context(&self) -> RuntimeContext {
+        self.context.clone()
+    }
+
+    /// Run `future` to completion on the runtime, e.g. to set up sessions before `run`.
+    pub fn block_on<F: Future>(&self, future: F) -> F::Output {
+        self.runtime.block_on(future)
+    }
+
+    /// Run `main` until it returns, a shutdown signal arrives or
+    /// [`RuntimeContext::shutdown`] is called, then shut down.
+    ///
+    /// An error returned by `main`, or from listening for signals, is passed on after
+    /// shutdown, in preference to [`RuntimeError::GracePeriodExceeded`].
+    pub fn run<F, Fut, E>(self, main: F) -> Result<(), RuntimeError>
+    where
+        F: FnOnce(RuntimeContext) -> Fut,
+        Fut: Future<Output = Result<(), E>> + Send + 'static,
+        E: Into<Box<dyn Error + Send + Sync>> + Send + 'static,
+    {
+        self.run_until(shutdown_signal(), main)
+    }
+
+    fn run_until<S, F, Fut, E>(self, signal: S, main: F) -> Result<(), RuntimeError>
+    where
+        S: Future<Output = std::io::Result<()>>,
+        F: FnOnce(RuntimeContext) -> Fut,
+        Fut: Future<Output = Result<(), E>> + Send + 'static,
+        E: Into<Box<dyn Error + Send + Sync>> + Send + 'static,
+    {
+        let Runtime {
+            runtime,
+            context,
+            grace_period,
+        } = self;
+        let result = runtime.block_on(async move {
+            let mut main = context.spawn(main(context.clone()));
+            let mut finished = None;
+            let mut signal_error = None;
+            tokio::select! {
+                signal = signal => signal_error = signal.err(),
+                _ = context.cancelled() => {}
+                result = &mut main => finished = Some(result),
+            }
+            context.shutdown();
+
+            let deadline = Instant::now() + grace_period;
+            context.tracker.close();
+            let tasks_done = timeout_at(deadline, context.tracker.wait()).await.is_ok();
+            // Every hook runs, even once an earlier one used up the grace period.
+            let mut hooks_done = true;
+            for hook in context.take_hooks() {
+                hooks_done &= timeout_at(deadline, hook()).await.is_ok();
+            }
+            // `main` is a tracked task, so it is done unless the tasks overran the deadline.
+            let finished = match finished {
+                Some(result) => Some(result),
+                None if main.is_finished() => Some(main.await),
+                None => None,
+            };
+            if let Some(e) = signal_error {
+                return Err(e.into());
+            }
+            // The application's own failure says more than a slow shutdown after it.
+            if let Some(result) = finished {
+                result?.map_err(|e| RuntimeError::Application(e.into()))?;
+            }
+            if !tasks_done || !hooks_done {
+                return Err(RuntimeError::GracePeriodExceeded(grace_period));
+            }
+            Ok(())
+        });
+        // Do not wait on blocking tasks that ignored the shutdown.
+        runtime.shutdown_background();
+        result
+    }
+
+    /// Run until a shutdown signal arrives or [`RuntimeContext::shutdown`] is called.
+    pub fn run_forever(self) -> Result<(), RuntimeError> {
+        self.run(|context| async move {
+            context.cancelled().await;
+            Ok::<_, std::convert::Infallible>(())
+        })
+    }
+}
+
+#[cfg(unix)]
+async fn shutdown_signal() -> std::io::Result<()> {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut terminate = signal(SignalKind::terminate())?;
+    tokio::select! {
+        interrupted = tokio::signal::ctrl_c() => interrupted,
+        _ = terminate.recv() => Ok(()),
+    }
+}
+
+#[cfg(not(unix))]
+async fn shutdown_signal() -> std::io::Result<()> {
+    tokio::signal::ctrl_c().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn test_shutdown_waits_for_tasks_then_hooks() {
+        let runtime = Runtime::new().unwrap();
+        let order = Arc::new(Mutex::new(Vec::new()));
+        let result = runtime.run(|context| {
+            let order = order.clone();
+            async move {
+                let worker = context.clone();
+                let task_order = order.clone();
+                context.spawn(async move {
+                    worker.cancelled().await;
+                    tokio::time::sleep(Duration::from_millis(20)).await;
+                    task_order.lock().unwrap().push("task");
+                });
+                let hook_order = order.clone();
+                context.on_shutdown(move || async move {
+                    hook_order.lock().unwrap().push("hook");
+                });
+                context.shutdown();
+                Ok::<_, std::io::Error>(())
+            }
+        });
+        assert!(result.is_ok());
+        assert_eq!(*order.lock().unwrap(), vec!["task", "hook"]);
+    }
+
+    #[test]
+    fn test_main_error_is_returned() {
+        let hooks_run = Arc::new(AtomicUsize::new(0));
+        let runtime = Runtime::new().unwrap();
+        let counter = hooks_run.clone();
+        runtime.context().on_shutdown(move || async move {
+            counter.fetch_add(1, Ordering::SeqCst);
+        });
+        let result = runtime.run(|_| async { Err("failed to start") });
+        assert!(matches!(
+            result,
+            Err(RuntimeError::Application(e)) if e.to_string() == "failed to start"
+        ));
+        assert_eq!(hooks_run.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_signal_error_still_shuts_down() {
+        let hooks_run = Arc::new(AtomicUsize::new(0));
+        let runtime = Runtime::new().unwrap();
+        let counter = hooks_run.clone();
+        runtime.context().on_shutdown(move || async move {
+            counter.fetch_add(1, Ordering::SeqCst);
+        });
+        let no_signals = async { Err(std::io::Error::other("no signal handlers")) };
+        let result = runtime.run_until(no_signals, |context| async move {
+            context.cancelled().await;
+            Ok::<_, std::io::Error>(())
+        });
+        assert!(
+            matches!(result, Err(RuntimeError::Io(e)) if e.to_string() == "no signal handlers")
+        );
+        assert_eq!(hooks_run.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_every_hook_runs_after_a_timeout() {
+        let hooks_run = Arc::new(AtomicUsize::new(0));
+        let runtime = Runtime::new()
+            .unwrap()
+            .with_grace_period(Duration::from_millis(50));
+        let context = runtime.context();
+        context.on_shutdown(std::future::pending::<()>);
+        let counter = hooks_run.clone();
+        context.on_shutdown(move || async move {
+            counter.fetch_add(1, Ordering::SeqCst);
+        });
+        let result = runtime.run(|context| async move {
+            context.shutdown();
+            Ok::<_, std::io::Error>(())
+        });
+        assert!(matches!(result, Err(RuntimeError::GracePeriodExceeded(_))));
+        assert_eq!(hooks_run.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_grace_period_exceeded() {
+        let runtime = Runtime::new()
+            .unwrap()
+            .with_grace_period(Duration::from_millis(50));
+        let result = runtime.run(|context| async move {
+            context.spawn(std::future::pending::<()>());
+            context.shutdown();
+            Ok::<_, std::io::Error>(())
+        });
+        assert!(matches!(
+            result,
+            Err(RuntimeError::GracePeriodExceeded(period)) if period == Duration::from_millis(50)
+        ));
+    }
+
+    #[test]
+    fn test_main_error_wins_over_grace_period() {
+        let runtime = Runtime::new()
+            .unwrap()
+            .with_grace_period(Duration::from_millis(50));
+        let result = runtime.run(|context| async move {
+            context.spawn(std::future::pending::<()>());
+            Err(std::io::Error::other("camera unplugged"))
+        });
+        assert!(matches!(
+            result,
+            Err(RuntimeError::Application(e)) if e.to_string() == "camera unplugged"
+        ));
+    }
+}
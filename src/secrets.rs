@@ -0,0 +1,589 @@
+//! Resolution of `{{ secret.NAME }}` and `{{ env.NAME }}` placeholders in the application
+//! config.
+//!
+//! Placeholders may appear anywhere in a string, any number of times, e.g.
+//! `postgres://user:{{ secret.DB_PW }}@{{ env.DB_HOST | default: "localhost" }}/db`.
+//! `\{{` stands for a literal `{{`. A string that is nothing but one placeholder, give or
+//! take surrounding whitespace, becomes the bare value.
+//!
+//! Secrets are looked up through a [`SecretProvider`]: files in a directory, environment
+//! variables, an in-memory map, or a [`SecretChain`] of those. What happens when a secret
+//! without a `default` cannot be resolved is decided per secret by a [`SecretPolicy`].
+
+use crate::config::{ConfigError, Result};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::PathBuf;
+use std::{env, fs, io};
+
+/// A placeholder at the start of the input: its source, name and optional quoted default.
+static PLACEHOLDER_PATTERN: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(
+        r#"^\{\{\s*(secret|env)\.([A-Za-z0-9_]+)\s*(?:\|\s*default:\s*"((?:[^"\\]|\\.)*)"\s*)?}}"#,
+    )
+    .unwrap()
+});
+
+/// Directory the platform mounts secrets into, one `<name>.secret` file per secret.
+pub const DEFAULT_SECRET_ROOT: &str = "/run/secrets";
+
+pub trait SecretProvider: Send + Sync {
+    /// The value of the secret `name`, or `None` if this provider does not have it.
+    fn get_secret(&self, name: &str) -> io::Result<Option<String>>;
+}
+
+/// Reads the secret `name` from `<root>/<name>.secret`, trimmed.
+#[derive(Clone, Debug)]
+pub struct FileSecretProvider {
+    root: PathBuf,
+}
+
+impl FileSecretProvider {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    pub fn root(&self) -> &std::path::Path {
+        &self.root
+    }
+}
+
+impl Default for FileSecretProvider {
+    fn default() -> Self {
+        Self::new(DEFAULT_SECRET_ROOT)
+    }
+}
+
+impl SecretProvider for FileSecretProvider {
+    fn get_secret(&self, name: &str) -> io::Result<Option<String>> {
+        match fs::read_to_string(self.root.join(format!("{name}.secret"))) {
+            Ok(value) => Ok(Some(value.trim().to_owned())),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+type EnvLookup = fn(&str) -> std::result::Result<String, env::VarError>;
+
+/// Reads the secret `name` from the environment variable `<prefix><name>`.
+#[derive(Clone, Debug)]
+pub struct EnvSecretProvider {
+    prefix: String,
+    lookup: EnvLookup,
+}
+
+impl Default for EnvSecretProvider {
+    fn default() -> Self {
+        Self::with_prefix("")
+    }
+}
+
+impl EnvSecretProvider {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_prefix(prefix: &str) -> Self {
+        Self {
+            prefix: prefix.to_string(),
+            lookup: |key| env::var(key),
+        }
+    }
+
+    /// Read variables through `lookup` instead of the process environment.
+    #[cfg(test)]
+    fn with_lookup(mut self, lookup: EnvLookup) -> Self {
+        self.lookup = lookup;
+        self
+    }
+}
+
+impl SecretProvider for EnvSecretProvider {
+    fn get_secret(&self, name: &str) -> io::Result<Option<String>> {
+        match (self.lookup)(&format!("{}{}", self.prefix, name)) {
+            Ok(value) => Ok(Some(value)),
+            Err(env::VarError::NotPresent) => Ok(None),
+            Err(e) => Err(io::Error::new(io::ErrorKind::InvalidData, e)),
+        }
+    }
+}
+
+/// Secrets held in memory, e.g. for tests.
+#[derive(Clone, Debug, Default)]
+pub struct MemorySecretProvider {
+    secrets: HashMap<String, String>,
+}
+
+impl MemorySecretProvider {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_secret(mut self, name: &str, value: &str) -> Self {
+        self.secrets.insert(name.to_string(), value.to_string());
+        self
+    }
+}
+
+impl<K: Into<String>, V: Into<String>> FromIterator<(K, V)> for MemorySecretProvider {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        Self {
+            secrets: iter
+                .into_iter()
+                .map(|(k, v)| (k.into(), v.into()))
+                .collect(),
+        }
+    }
+}
+
+impl SecretProvider for MemorySecretProvider {
+    fn get_secret(&self, name: &str) -> io::Result<Option<String>> {
+        Ok(self.secrets.get(name).cloned())
+    }
+}
+
+/// Asks its providers in order and returns the first secret found.
+///
+/// A provider failing with an error stops the lookup.
+#[derive(Default)]
+pub struct SecretChain {
+    providers: Vec<Box<dyn SecretProvider>>,
+}
+
+impl SecretChain {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with(mut self, provider: impl SecretProvider + 'static) -> Self {
+        self.providers.push(Box::new(provider));
+        self
+    }
+}
+
+impl SecretProvider for SecretChain {
+    fn get_secret(&self, name: &str) -> io::Result<Option<String>> {
+        for provider in &self.providers {
+            if let Some(value) = provider.get_secret(name)? {
+                return Ok(Some(value));
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// What to do with a placeholder whose secret no provider has.
+///
+/// A provider failing to read a secret is an error under every policy, see
+/// [`ConfigError::Secret`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum SecretPolicy {
+    /// Fail to load the config with [`ConfigError::Unresolved`].
+    #[default]
+    Fail,
+    /// Leave the placeholder in the config as it is.
+    KeepPlaceholder,
+    /// Use this value instead.
+    Default(String),
+}
+
+/// Replaces `{{ secret.NAME }}` placeholders with secrets from a [`SecretProvider`] and
+/// `{{ env.NAME }}` placeholders with environment variables.
+///
+/// The default resolver reads [`DEFAULT_SECRET_ROOT`] and fails on any missing secret.
+/// Environment variables without a `default` must be set.
+pub struct SecretResolver {
+    provider: Box<dyn SecretProvider>,
+    env: Box<dyn SecretProvider>,
+    default_policy: SecretPolicy,
+    policies: HashMap<String, SecretPolicy>,
+}
+
+impl Default for SecretResolver {
+    fn default() -> Self {
+        Self::new(FileSecretProvider::default())
+    }
+}
+
+impl SecretResolver {
+    pub fn new(provider: impl SecretProvider + 'static) -> Self {
+        Self {
+            provider: Box::new(provider),
+            env: Box::new(EnvSecretProvider::new()),
+            default_policy: SecretPolicy::default(),
+            policies: HashMap::new(),
+        }
+    }
+
+    /// Read secret files from `root` instead of [`DEFAULT_SECRET_ROOT`].
+    pub fn from_root(root: impl Into<PathBuf>) -> Self {
+        Self::new(FileSecretProvider::new(root))
+    }
+
+    /// Policy for every secret without one of its own.
+    pub fn with_default_policy(mut self, policy: SecretPolicy) -> Self {
+        self.default_policy = policy;
+        self
+    }
+
+    /// Policy for the secret `name`.
+    pub fn with_policy(mut self, name: &str, policy: SecretPolicy) -> Self {
+        self.policies.insert(name.to_string(), policy);
+        self
+    }
+
+    fn policy(&self, name: &str) -> &SecretPolicy {
+        self.policies.get(name).unwrap_or(&self.default_policy)
+    }
+
+    /// Look up the environment variables of `{{ env.NAME }}` placeholders through
+    /// `provider` rather than the process environment.
+    pub fn with_env_provider(mut self, provider: impl SecretProvider + 'static) -> Self {
+        self.env = Box::new(provider);
+        self
+    }
+
+    fn lookup(&self, placeholder: &Placeholder) -> Result<Resolution> {
+        let is_secret = placeholder.source == "secret";
+        let provider = if is_secret { &self.provider } else { &self.env };
+        let found =
+            provider
+                .get_secret(&placeholder.name)
+                .map_err(|source| ConfigError::Secret {
+                    name: if is_secret {
+                        placeholder.name.clone()
+                    } else {
+                        format!("env.{}", placeholder.name)
+                    },
+                    source,
+                })?;
+        if let Some(value) = found.or_else(|| placeholder.default.clone()) {
+            return Ok(Resolution::Value(value));
+        }
+        if !is_secret {
+            return Ok(Resolution::Unresolved);
+        }
+        Ok(match self.policy(&placeholder.name) {
+            SecretPolicy::Fail => Resolution::Unresolved,
+            SecretPolicy::KeepPlaceholder => Resolution::Keep,
+            SecretPolicy::Default(value) => Resolution::Value(value.clone()),
+        })
+    }
+
+    /// Replace every placeholder in the strings of `value`, recursively.
+    ///
+    /// Fails with [`ConfigError::Unresolved`] listing every placeholder that could not be
+    /// resolved, or with [`ConfigError::Secret`] if a secret provider fails.
+    pub fn resolve(&self, value: Value) -> Result<Value> {
+        let mut unresolved = Vec::new();
+        let value = self.resolve_at(value, &mut String::new(), &mut unresolved)?;
+        if unresolved.is_empty() {
+            Ok(value)
+        } else {
+            Err(ConfigError::Unresolved(unresolved))
+        }
+    }
+
+    fn resolve_at(
+        &self,
+        value: Value,
+        path: &mut String,
+        unresolved: &mut Vec<UnresolvedPlaceholder>,
+    ) -> Result<Value> {
+        let len = path.len();
+        let value = match value {
+            Value::Object(map) => {
+                let mut new_map = serde_json::Map::new();
+                for (k, v) in map {
+                    path.push('/');
+                    path.push_str(&k.replace('~', "~0").replace('/', "~1"));
+                    let v = self.resolve_at(v, path, unresolved)?;
+                    path.truncate(len);
+                    new_map.insert(k, v);
+                }
+                Value::Object(new_map)
+            }
+            Value::Array(arr) => {
+                let mut new_arr = Vec::with_capacity(arr.len());
+                for (i, v) in arr.into_iter().enumerate() {
+                    path.push_str(&format!("/{i}"));
+                    new_arr.push(self.resolve_at(v, path, unresolved)?);
+                    path.truncate(len);
+                }
+                Value::Array(new_arr)
+            }
+            Value::String(s) => Value::String(self.interpolate(s, path, unresolved)?),
+            other => other,
+        };
+        Ok(value)
+    }
+
+    fn interpolate(
+        &self,
+        s: String,
+        path: &str,
+        unresolved: &mut Vec<UnresolvedPlaceholder>,
+    ) -> Result<String> {
+        let mut resolve = |raw: &str, placeholder: Placeholder| -> Result<Option<String>> {
+            Ok(match self.lookup(&placeholder)? {
+                Resolution::Value(value) => Some(value),
+                Resolution::Keep => None,
+                Resolution::Unresolved => {
+                    unresolved.push(UnresolvedPlaceholder {
+                        path: path.to_string(),
+                        placeholder: raw.to_string(),
+                    });
+                    None
+                }
+            })
+        };
+
+        let trimmed = s.trim();
+        if let Some((raw, placeholder)) = Placeholder::parse(trimmed) {
+            if raw.len() == trimmed.len() {
+                return Ok(resolve(raw, placeholder)?.unwrap_or(s));
+            }
+        }
+
+        let mut out = String::with_capacity(s.len());
+        let mut rest = s.as_str();
+        while let Some(start) = rest.find("{{") {
+            if rest[..start].ends_with('\\') {
+                out.push_str(&rest[..start - 1]);
+                out.push_str("{{");
+                rest = &rest[start + 2..];
+                continue;
+            }
+            out.push_str(&rest[..start]);
+            rest = &rest[start..];
+            match Placeholder::parse(rest) {
+                Some((raw, placeholder)) => {
+                    let value = resolve(raw, placeholder)?;
+                    out.push_str(value.as_deref().unwrap_or(raw));
+                    rest = &rest[raw.len()..];
+                }
+                None => {
+                    out.push_str("{{");
+                    rest = &rest[2..];
+                }
+            }
+        }
+        out.push_str(rest);
+        Ok(out)
+    }
+}
+
+struct Placeholder {
+    /// `secret` or `env`.
+    source: String,
+    name: String,
+    default: Option<String>,
+}
+
+impl Placeholder {
+    /// The placeholder `input` starts with, and its source text.
+    fn parse(input: &str) -> Option<(&str, Self)> {
+        let caps = PLACEHOLDER_PATTERN.captures(input)?;
+        let default = caps.get(3).map(|default| {
+            let mut unescaped = String::with_capacity(default.len());
+            let mut chars = default.as_str().chars();
+            while let Some(c) = chars.next() {
+                match c {
+                    '\\' => unescaped.extend(chars.next()),
+                    c => unescaped.push(c),
+                }
+            }
+            unescaped
+        });
+        let placeholder = Self {
+            source: caps[1].to_string(),
+            name: caps[2].to_string(),
+            default,
+        };
+        Some((caps.get(0)?.as_str(), placeholder))
+    }
+}
+
+enum Resolution {
+    Value(String),
+    Keep,
+    Unresolved,
+}
+
+/// A placeholder left unresolved, see [`ConfigError::Unresolved`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UnresolvedPlaceholder {
+    /// JSON pointer to the string within the config section.
+    pub path: String,
+    /// The placeholder as written, e.g. `{{ secret.DB_PW }}`.
+    pub placeholder: String,
+}
+
+impl fmt::Display for UnresolvedPlaceholder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at '{}'", self.placeholder, self.path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_chain_uses_first_provider_with_secret() {
+        let chain = SecretChain::new()
+            .with(MemorySecretProvider::new().with_secret("A", "memory"))
+            .with(MemorySecretProvider::from_iter([
+                ("A", "shadowed"),
+                ("B", "second"),
+            ]));
+        assert_eq!(chain.get_secret("A").unwrap().as_deref(), Some("memory"));
+        assert_eq!(chain.get_secret("B").unwrap().as_deref(), Some("second"));
+        assert_eq!(chain.get_secret("C").unwrap(), None);
+    }
+
+    /// A provider whose secrets cannot be read.
+    struct UnreadableProvider;
+
+    impl SecretProvider for UnreadableProvider {
+        fn get_secret(&self, _name: &str) -> io::Result<Option<String>> {
+            Err(io::Error::from(io::ErrorKind::PermissionDenied))
+        }
+    }
+
+    #[test]
+    fn test_env_provider() {
+        let provider =
+            EnvSecretProvider::with_prefix("MAKE87_TEST_SECRET_").with_lookup(|key| match key {
+                "MAKE87_TEST_SECRET_TOKEN" => Ok("from-env".to_string()),
+                "MAKE87_TEST_SECRET_BINARY" => Err(env::VarError::NotUnicode(Default::default())),
+                _ => Err(env::VarError::NotPresent),
+            });
+        assert_eq!(
+            provider.get_secret("TOKEN").unwrap().as_deref(),
+            Some("from-env")
+        );
+        assert_eq!(provider.get_secret("OTHER").unwrap(), None);
+        assert_eq!(
+            provider.get_secret("BINARY").unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        );
+    }
+
+    #[test]
+    fn test_unreadable_secret_fails_under_every_policy() {
+        for policy in [
+            SecretPolicy::Fail,
+            SecretPolicy::KeepPlaceholder,
+            SecretPolicy::Default("d".into()),
+        ] {
+            let resolver = SecretResolver::new(UnreadableProvider).with_default_policy(policy);
+            let err = resolver
+                .resolve(json!({"key": "{{ secret.DB_PW }}"}))
+                .unwrap_err();
+            assert!(matches!(
+                err,
+                ConfigError::Secret { ref name, ref source }
+                    if name == "DB_PW" && source.kind() == io::ErrorKind::PermissionDenied
+            ));
+        }
+    }
+
+    #[test]
+    fn test_secret_policies() {
+        let resolver = SecretResolver::new(MemorySecretProvider::new().with_secret("KNOWN", "v"))
+            .with_default_policy(SecretPolicy::KeepPlaceholder)
+            .with_policy("FALLBACK", SecretPolicy::Default("d".into()))
+            .with_policy("REQUIRED", SecretPolicy::Fail);
+
+        let resolved = resolver
+            .resolve(json!({
+                "known": "{{ secret.KNOWN }}",
+                "list": ["{{ secret.FALLBACK }}", "{{ secret.OTHER }}", 1],
+            }))
+            .unwrap();
+        assert_eq!(
+            resolved,
+            json!({"known": "v", "list": ["d", "{{ secret.OTHER }}", 1]})
+        );
+
+        let err = resolver
+            .resolve(json!({"key": "{{ secret.REQUIRED }}"}))
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            ConfigError::Unresolved(ref placeholders)
+                if placeholders[0].placeholder == "{{ secret.REQUIRED }}"
+        ));
+    }
+
+    #[test]
+    fn test_inline_interpolation() {
+        let resolver = SecretResolver::new(MemorySecretProvider::new().with_secret("DB_PW", "pw"))
+            .with_env_provider(MemorySecretProvider::new().with_secret("DB_HOST", "db"));
+
+        let resolved = resolver
+            .resolve(json!({
+                "url": "postgres://user:{{ secret.DB_PW }}@{{env.DB_HOST}}/db",
+                "port": "{{ env.DB_PORT | default: \"5432\" }}",
+                "quoted": "{{ env.MISSING | default: \"say \\\"hi\\\"\" }}!",
+                "template": "\\{{ secret.DB_PW }} and {{ not.a.placeholder }}",
+                "bare": "  {{ secret.DB_PW }}\n",
+            }))
+            .unwrap();
+        assert_eq!(
+            resolved,
+            json!({
+                "url": "postgres://user:pw@db/db",
+                "port": "5432",
+                "quoted": "say \"hi\"!",
+                "template": "{{ secret.DB_PW }} and {{ not.a.placeholder }}",
+                "bare": "pw",
+            })
+        );
+    }
+
+    #[test]
+    fn test_unreadable_env_variable_fails() {
+        let resolver =
+            SecretResolver::new(MemorySecretProvider::new()).with_env_provider(UnreadableProvider);
+        let err = resolver
+            .resolve(json!({"host": "{{ env.HOST | default: \"localhost\" }}"}))
+            .unwrap_err();
+        assert!(matches!(err, ConfigError::Secret { ref name, .. } if name == "env.HOST"));
+    }
+
+    #[test]
+    fn test_every_unresolved_placeholder_is_listed() {
+        let resolver = SecretResolver::new(MemorySecretProvider::new())
+            .with_env_provider(MemorySecretProvider::new())
+            .with_policy("OPTIONAL", SecretPolicy::KeepPlaceholder);
+
+        let err = resolver
+            .resolve(json!({
+                "a/b": "{{ secret.USER }}:{{ secret.OPTIONAL }}@{{ env.HOST }}",
+                "list": [1, "x{{ secret.TOKEN }}"],
+            }))
+            .unwrap_err();
+        let ConfigError::Unresolved(placeholders) = &err else {
+            panic!("Expected unresolved placeholders, got {err:?}");
+        };
+        let listed: Vec<_> = placeholders.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            listed,
+            vec![
+                "{{ secret.USER }} at '/a~1b'",
+                "{{ env.HOST }} at '/a~1b'",
+                "{{ secret.TOKEN }} at '/list/1'",
+            ]
+        );
+        assert!(err
+            .to_string()
+            .starts_with("unresolved config placeholders: "));
+    }
+}
@@ -0,0 +1,329 @@
+//! Fixtures for testing make87 applications in a single process.
+//!
+//! [`AppFixture`] builds the [`ApplicationConfig`] an application would receive through
+//! `MAKE87_CONFIG`. Its interfaces are isolated: their sessions neither listen, connect
+//! nor scout, so tests never touch real ports or each other. [`wire`] then connects two
+//! fixtures over a unique unix socket.
+//!
+//! ```ignore
+//! let mut publisher = AppFixture::new("camera").publisher("IMAGE", "camera/image");
+//! let mut subscriber = AppFixture::new("detector").subscriber("IMAGE", "camera/image");
+//! wire(&mut publisher, &mut subscriber);
+//! let publisher = publisher.zenoh_interface();
+//! let subscriber = subscriber.zenoh_interface();
+//! ```
+
+use crate::models::{
+    AccessPoint, ApplicationConfig, ApplicationConfigBuilder, EntityBuilder, InterfaceBuilder,
+    InterfaceConfig, InterfaceEntity,
+};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+
+/// Interface entities are added to until [`AppFixture::interface`] selects another one.
+pub const DEFAULT_INTERFACE: &str = "zenoh";
+
+/// Message type given to every entity of a fixture.
+pub const TEST_MESSAGE_TYPE: &str = "make87.testing.Message";
+
+/// Encoding given to every entity of a fixture.
+pub const TEST_ENCODING: &str = "json";
+
+/// Builds the config of an application under test.
+#[derive(Clone)]
+pub struct AppFixture {
+    config: ApplicationConfig,
+    interface: String,
+}
+
+impl AppFixture {
+    pub fn new(app_name: &str) -> Self {
+        let config = ApplicationConfig::builder()
+            .app(app_name)
+            .system_id("test-system")
+            .build();
+        Self {
+            config,
+            interface: DEFAULT_INTERFACE.to_string(),
+        }
+    }
+
+    /// Add the following entities to the interface `name`.
+    pub fn interface(mut self, name: &str) -> Self {
+        self.interface = name.to_string();
+        self
+    }
+
+    /// Set the application's own config, as read from `config`.
+    pub fn config(mut self, config: Value) -> Self {
+        self.config.config = config;
+        self
+    }
+
+    /// Set keys of the current interface's config, such as `shm` or `listen_policy`.
+    pub fn interface_config(mut self, settings: Value) -> Self {
+        merge(&mut self.current_interface().config, settings);
+        self
+    }
+
+    /// Set keys of the entity `name` on the current interface, such as `history_depth` or
+    /// `handler`.
+    ///
+    /// # Panics
+    ///
+    /// If the current interface has no entity called `name`.
+    pub fn entity_config(mut self, name: &str, settings: Value) -> Self {
+        let iface = self.current_interface();
+        let config = if let Some(p) = iface.publishers.get_mut(name) {
+            &mut p.config
+        } else if let Some(s) = iface.subscribers.get_mut(name) {
+            &mut s.config.config
+        } else if let Some(r) = iface.requesters.get_mut(name) {
+            &mut r.config.config
+        } else if let Some(p) = iface.providers.get_mut(name) {
+            &mut p.config
+        } else {
+            panic!("No entity '{name}' on interface '{}'", iface.name);
+        };
+        merge(config, settings);
+        self
+    }
+
+    pub fn publisher(self, name: &str, topic_key: &str) -> Self {
+        self.add(|i| i.publisher(name, topic_key).message_type(TEST_MESSAGE_TYPE))
+    }
+
+    pub fn subscriber(self, name: &str, topic_key: &str) -> Self {
+        self.add(|i| {
+            i.subscriber(name, topic_key)
+                .message_type(TEST_MESSAGE_TYPE)
+                .access_point(loopback_access_point())
+        })
+    }
+
+    pub fn requester(self, name: &str, endpoint_key: &str) -> Self {
+        self.add(|i| {
+            i.requester(name, endpoint_key)
+                .message_types(TEST_MESSAGE_TYPE, TEST_MESSAGE_TYPE)
+                .access_point(loopback_access_point())
+        })
+    }
+
+    pub fn provider(self, name: &str, endpoint_key: &str) -> Self {
+        self.add(|i| {
+            i.provider(name, endpoint_key)
+                .message_types(TEST_MESSAGE_TYPE, TEST_MESSAGE_TYPE)
+        })
+    }
+
+    /// Let the current interface's session listen on `locator`.
+    pub fn listen_on(mut self, locator: &str) -> Self {
+        push_endpoint(self.current_interface(), "listen_endpoints", locator);
+        self
+    }
+
+    /// Let the current interface's session connect to `locator`.
+    pub fn connect_to(mut self, locator: &str) -> Self {
+        push_endpoint(self.current_interface(), "connect_endpoints", locator);
+        self
+    }
+
+    pub fn build(self) -> ApplicationConfig {
+        self.config
+    }
+
+    /// The config as JSON, ready to be passed to an application binary as `MAKE87_CONFIG`.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(&self.config).expect("application config is serializable")
+    }
+
+    /// A [`ZenohInterface`](crate::interfaces::zenoh::ZenohInterface) for the current
+    /// interface.
+    #[cfg(feature = "zenoh")]
+    pub fn zenoh_interface(&self) -> crate::interfaces::zenoh::ZenohInterface {
+        crate::interfaces::zenoh::ZenohInterface::new(self.config.clone(), &self.interface)
+    }
+
+    /// Add the entity built by `entity` to the current interface, in [`TEST_ENCODING`].
+    fn add<F, E>(mut self, entity: F) -> Self
+    where
+        F: FnOnce(InterfaceBuilder) -> EntityBuilder<E>,
+        E: InterfaceEntity,
+    {
+        self.current_interface();
+        let name = self.interface.clone();
+        self.config = ApplicationConfigBuilder::from(self.config)
+            .zenoh(&name, |i| entity(i).encoding(Some(TEST_ENCODING)))
+            .build();
+        self
+    }
+
+    fn current_interface(&mut self) -> &mut InterfaceConfig {
+        self.config
+            .interfaces
+            .entry(self.interface.clone())
+            .or_insert_with_key(|name| isolated_interface(name))
+    }
+}
+
+fn isolated_interface(name: &str) -> InterfaceConfig {
+    let mut interface = InterfaceConfig::new(name);
+    merge(
+        &mut interface.config,
+        json!({
+            "listen_endpoints": [],
+            "connect_endpoints": [],
+            "multicast_scouting": false,
+        }),
+    );
+    interface
+}
+
+/// Never connected to: isolated interfaces take their endpoints from `connect_endpoints`.
+fn loopback_access_point() -> AccessPoint {
+    AccessPoint {
+        vpn_ip: "127.0.0.1".to_string(),
+        vpn_port: 0,
+        public_ip: None,
+        public_port: None,
+        same_node: true,
+    }
+}
+
+fn merge(config: &mut BTreeMap<String, Value>, settings: Value) {
+    let Value::Object(settings) = settings else {
+        panic!("Settings must be a JSON object, got {settings}");
+    };
+    config.extend(settings);
+}
+
+fn push_endpoint(iface: &mut InterfaceConfig, key: &str, locator: &str) {
+    let endpoints = iface.config.entry(key.to_string()).or_insert(json!([]));
+    match endpoints {
+        Value::Array(endpoints) => endpoints.push(json!(locator)),
+        _ => *endpoints = json!([locator]),
+    }
+}
+
+/// A unix socket locator no other session in this or any other process uses.
+///
+/// The sockets live in a temporary directory of their own, kept for the rest of the
+/// process since sessions may outlive the test that wired them.
+#[cfg(unix)]
+pub fn loopback_locator() -> String {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::OnceLock;
+    use tempfile::TempDir;
+
+    static SOCKETS: OnceLock<TempDir> = OnceLock::new();
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+    let dir = SOCKETS.get_or_init(|| {
+        TempDir::with_prefix("make87-").expect("failed to create a socket directory")
+    });
+    let path = dir
+        .path()
+        .join(format!("{}.sock", NEXT.fetch_add(1, Ordering::Relaxed)));
+    format!("unixsock-stream/{}", path.display())
+}
+
+/// Connect the current interfaces of `listener` and `connector` over a fresh
+/// [`loopback_locator`]. Either side may publish, subscribe, query or reply.
+#[cfg(unix)]
+pub fn wire(listener: &mut AppFixture, connector: &mut AppFixture) {
+    let locator = loopback_locator();
+    *listener = listener.clone().listen_on(&locator);
+    *connector = connector.clone().connect_to(&locator);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::load_config_from_json;
+
+    #[test]
+    fn test_fixture_builds_isolated_config() {
+        let fixture = AppFixture::new("detector")
+            .config(json!({"threshold": 0.5}))
+            .publisher("DETECTIONS", "detector/detections")
+            .entity_config("DETECTIONS", json!({"history_depth": 3}))
+            .interface("other")
+            .subscriber("IMAGE", "camera/image")
+            .requester("DESCRIBE", "describe")
+            .provider("STATUS", "status");
+
+        let config = load_config_from_json(fixture.to_json()).unwrap();
+        assert_eq!(config.application_info.application_name, "detector");
+        assert_eq!(config.config["threshold"], json!(0.5));
+        let zenoh = &config.interfaces["zenoh"];
+        assert_eq!(
+            zenoh.publishers["DETECTIONS"].config["history_depth"],
+            json!(3)
+        );
+        assert_eq!(zenoh.config["listen_endpoints"], json!([]));
+        let other = &config.interfaces["other"];
+        assert_eq!(other.subscribers["IMAGE"].config.interface_name, "other");
+        assert!(other.requesters.contains_key("DESCRIBE"));
+        assert!(other.providers.contains_key("STATUS"));
+    }
+
+    #[test]
+    fn test_wire_adds_unique_locator() {
+        let mut publisher = AppFixture::new("a").publisher("OUT", "a/out");
+        let mut subscriber = AppFixture::new("b").subscriber("IN", "a/out");
+        wire(&mut publisher, &mut subscriber);
+        wire(&mut publisher, &mut subscriber);
+
+        let listen = &publisher.config.interfaces["zenoh"].config["listen_endpoints"];
+        let connect = &subscriber.config.interfaces["zenoh"].config["connect_endpoints"];
+        assert_eq!(listen, connect);
+        assert_eq!(listen.as_array().unwrap().len(), 2);
+        assert_ne!(listen[0], listen[1]);
+        assert!(listen[0].as_str().unwrap().starts_with("unixsock-stream/"));
+    }
+
+    #[cfg(feature = "zenoh")]
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn test_isolated_session_config() {
+        let iface = AppFixture::new("app")
+            .subscriber("IN", "isolated/in")
+            .zenoh_interface();
+        assert!(iface.connect_endpoints().unwrap().is_empty());
+        let cfg = iface.zenoh_config().unwrap();
+        assert_eq!(cfg.get_json("listen/endpoints").unwrap(), "[]");
+        assert_eq!(cfg.get_json("connect/endpoints").unwrap(), "[]");
+        assert_eq!(cfg.get_json("scouting/multicast/enabled").unwrap(), "false");
+    }
+
+    #[cfg(feature = "zenoh")]
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn test_wired_apps_exchange_samples() {
+        use std::time::Duration;
+
+        let mut publisher = AppFixture::new("publisher").publisher("OUT", "wired/topic");
+        let mut subscriber = AppFixture::new("subscriber").subscriber("IN", "wired/topic");
+        wire(&mut publisher, &mut subscriber);
+        let publisher = publisher.zenoh_interface();
+        let subscriber = subscriber.zenoh_interface();
+
+        let zenoh_publisher = publisher.publisher("OUT").await.unwrap();
+        let zenoh_subscriber = subscriber.subscriber("IN").await.unwrap();
+        let received = tokio::time::timeout(Duration::from_secs(10), async {
+            loop {
+                zenoh_publisher.put("hello").await.unwrap();
+                if let Ok(Some(sample)) =
+                    tokio::time::timeout(Duration::from_millis(100), zenoh_subscriber.recv_async())
+                        .await
+                        .map(Result::ok)
+                {
+                    return sample;
+                }
+            }
+        })
+        .await
+        .expect("no sample crossed the loopback");
+        assert_eq!(received.payload().to_bytes(), b"hello".as_slice());
+
+        publisher.close().await.unwrap();
+        subscriber.close().await.unwrap();
+    }
+}
@@ -78,6 +78,7 @@ fn test_pub_sub_defaults_only() {
                 providers: Default::default(),
                 clients: Default::default(),
                 servers: Default::default(),
+                config: Default::default(),
             },
         )]),
         peripherals: MountedPeripherals {
@@ -79,6 +79,7 @@ fn test_req_prv_defaults_only() {
                 providers: Default::default(),
                 clients: Default::default(),
                 servers: Default::default(),
+                config: Default::default(),
             },
         )]),
         peripherals: MountedPeripherals {